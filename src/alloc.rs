@@ -1,6 +1,12 @@
 use std::alloc;
+use std::cell::Cell;
+use std::fmt;
+use std::ops::AddAssign;
 use crate::stats::STATS;
 
+#[cfg(test)]
+mod tests;
+
 struct CustomAlloc {}
 
 unsafe impl alloc::GlobalAlloc for CustomAlloc {
@@ -9,16 +15,115 @@ unsafe impl alloc::GlobalAlloc for CustomAlloc {
         if !ret.is_null() {
             STATS.alloc_bytes.inc_by(layout.size());
             STATS.alloc_calls.inc();
+            record(|counts| {
+                counts.bytes += layout.size();
+                counts.calls += 1;
+                counts.live += layout.size() as isize;
+                counts.peak = counts.peak.max(counts.live);
+            });
         }
-        return ret
+        ret
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
         alloc::System.dealloc(ptr, layout);
         STATS.dealloc_bytes.inc_by(layout.size());
         STATS.dealloc_calls.inc();
+        record(|counts| counts.live -= layout.size() as isize);
     }
 }
 
 #[global_allocator]
 static ALLOCATOR: CustomAlloc = CustomAlloc{};
+
+/// Allocations of a thread since it started. Live bytes are what the thread
+/// allocated minus what it freed, memory freed by another thread than the
+/// one allocating it is not accounted for.
+#[derive(Clone, Copy)]
+struct Counts {
+    bytes: usize,
+    calls: usize,
+    live: isize,
+    peak: isize,
+}
+
+thread_local! {
+    static COUNTS: Cell<Counts> = const { Cell::new(Counts{ bytes: 0, calls: 0, live: 0, peak: 0 }) };
+}
+
+/// Updates the counts of the current thread. Allocations made while the
+/// thread is torn down are skipped.
+fn record<F: FnOnce(&mut Counts)>(update: F) {
+    let _ = COUNTS.try_with(|cell| {
+        let mut counts = cell.get();
+        update(&mut counts);
+        cell.set(counts);
+    });
+}
+
+fn counts() -> Counts {
+    COUNTS.try_with(Cell::get).unwrap_or(Counts{ bytes: 0, calls: 0, live: 0, peak: 0 })
+}
+
+/// Memory allocated over a scope.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Usage {
+    pub bytes: usize,
+    pub calls: usize,
+    /// Most bytes live at once, above what was live when the scope started.
+    pub peak: usize,
+    /// Bytes allocated and not freed yet.
+    pub live: isize,
+}
+
+/// Adds up the usage of threads running at the same time, their peaks may
+/// have been at once.
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.bytes += other.bytes;
+        self.calls += other.calls;
+        self.peak += other.peak;
+        self.live += other.live;
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bytes: {}, calls: {}, peak: {}", self.bytes, self.calls, self.peak)
+    }
+}
+
+/// Tracks what the current thread allocates from when it is started. Scopes
+/// do not nest, starting one resets the peak of any other on the thread.
+pub struct Scope {
+    start: Counts,
+}
+
+impl Scope {
+    pub fn start() -> Scope {
+        record(|counts| counts.peak = counts.live);
+        Scope{ start: counts() }
+    }
+
+    pub fn usage(&self) -> Usage {
+        let now = counts();
+        Usage{
+            bytes: now.bytes - self.start.bytes,
+            calls: now.calls - self.start.calls,
+            peak: (now.peak - self.start.live).max(0) as usize,
+            live: now.live - self.start.live,
+        }
+    }
+}
+
+/// Accounts what another thread allocated on behalf of the current one, with
+/// its peak on top of what is live here now. What it left allocated is freed
+/// here.
+pub fn absorb(usage: Usage) {
+    record(|counts| {
+        counts.bytes += usage.bytes;
+        counts.calls += usage.calls;
+        counts.peak = counts.peak.max(counts.live + usage.peak as isize);
+        counts.live += usage.live;
+    });
+}
@@ -0,0 +1,40 @@
+use super::*;
+use std::hint::black_box;
+use std::thread;
+
+#[test]
+fn scope_tracks_thread_allocations() {
+    let scope = Scope::start();
+    let kept = black_box(vec![0u8; 4096]);
+    drop(black_box(vec![0u8; 8192]));
+    let usage = scope.usage();
+    assert!(usage.bytes >= 4096 + 8192, "{}", usage);
+    assert!(usage.calls >= 2, "{}", usage);
+    assert!(
+        usage.peak >= 8192 && usage.peak < 4096 + 8192 + 1024,
+        "{}",
+        usage
+    );
+    assert!(usage.live >= 4096 && usage.live < 8192, "{:?}", usage);
+    drop(kept);
+}
+
+#[test]
+fn absorbs_other_threads() {
+    let scope = Scope::start();
+    let (buf, usage) = thread::spawn(|| {
+        let scope = Scope::start();
+        let buf = black_box(vec![0u8; 1 << 16]);
+        (buf, scope.usage())
+    })
+    .join()
+    .unwrap();
+    absorb(usage);
+    drop(buf);
+
+    let usage = scope.usage();
+    assert!(usage.bytes >= 1 << 16, "{}", usage);
+    assert!(usage.peak >= 1 << 16, "{}", usage);
+    // Freed here after being allocated there.
+    assert!(usage.live.abs() < 1024, "{:?}", usage);
+}
@@ -1,14 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct RootResponse {
-    pub api_version: &'static str,
-    pub author: &'static str,
-    pub color: &'static str,
-    pub head: &'static str,
-    pub tail: &'static str,
-    pub version: &'static str,
+pub struct RootResponse<'a> {
+    pub api_version: &'a str,
+    pub author: &'a str,
+    pub color: &'a str,
+    pub head: &'a str,
+    pub tail: &'a str,
+    pub version: &'a str,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -18,6 +23,11 @@ pub struct MoveResponse {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorResponse<'a> {
+    pub error: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum Direction {
     Up,
@@ -26,10 +36,24 @@ pub enum Direction {
     Right,
 }
 
-#[derive(Deserialize, Debug)]
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Up => write!(f, "up"),
+            Direction::Down => write!(f, "down"),
+            Direction::Left => write!(f, "left"),
+            Direction::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// Text is borrowed from the request body, or copied when it has escapes, like
+/// a shout with quotes in it.
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct MoveRequest<'a> {
-    pub game: Game,
+    #[serde(borrow)]
+    pub game: Game<'a>,
     pub turn: u16,
 
     #[serde(borrow)]
@@ -39,34 +63,134 @@ pub struct MoveRequest<'a> {
     pub you: Snake<'a>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct Game {
+pub struct Game<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+
+    #[serde(borrow)]
+    pub ruleset: Ruleset<'a>,
+    #[serde(borrow, default)]
+    pub map: Cow<'a, str>,
     pub timeout: u16,
+    #[serde(borrow, default)]
+    pub source: Cow<'a, str>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Ruleset<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub version: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub settings: RulesetSettings<'a>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RulesetSettings<'a> {
+    pub food_spawn_chance: u16,
+    pub minimum_food: u16,
+    /// Missing from older engines, which leave it out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hazard_damage_per_turn: Option<u16>,
+    #[serde(borrow)]
+    pub hazard_map: Cow<'a, str>,
+    #[serde(borrow)]
+    pub hazard_map_author: Cow<'a, str>,
+    pub royale: RoyaleSettings,
+    pub squad: SquadSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RoyaleSettings {
+    pub shrink_every_n_turns: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SquadSettings {
+    pub allow_body_collisions: bool,
+    pub shared_elimination: bool,
+    pub shared_health: bool,
+    pub shared_length: bool,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Board<'a> {
     pub height: u16,
     pub width: u16,
     pub food: Vec<Point>,
+    #[serde(default)]
     pub hazards: Vec<Point>,
 
     #[serde(borrow)]
     pub snakes: Vec<Snake<'a>>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Snake<'a> {
-    pub id: &'a str,
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub name: Cow<'a, str>,
     pub health: u16,
-    pub length: u16,
     pub body: Vec<Point>,
+    #[serde(borrow, default)]
+    pub latency: Cow<'a, str>,
+    pub head: Point,
+    pub length: u16,
+    #[serde(borrow, default)]
+    pub shout: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub squad: Cow<'a, str>,
+
+    #[serde(borrow, default)]
+    pub customizations: Customizations<'a>,
+}
+
+impl MoveRequest<'static> {
+    /// Parses a request copying its text, for a request that outlives the
+    /// body, like one handed to another thread.
+    pub fn parse_owned(body: &[u8]) -> serde_json::Result<MoveRequest<'static>> {
+        // Read as a stream, the parser has nothing to borrow from.
+        let mut de = serde_json::Deserializer::from_reader(body);
+        let req = MoveRequest::deserialize(&mut de)?;
+        de.end()?;
+        Ok(req)
+    }
+}
+
+impl<'a> Snake<'a> {
+    /// Latency of the previous response in milliseconds. The engine sends it
+    /// as a string and leaves it empty (or "0") on the first turn and after
+    /// a timeout.
+    pub fn latency_ms(&self) -> Option<u32> {
+        match self.latency.parse() {
+            Ok(0) | Err(_) => None,
+            Ok(ms) => Some(ms),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Customizations<'a> {
+    #[serde(borrow, default)]
+    pub color: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub head: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub tail: Cow<'a, str>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Point {
     pub x: i16,
@@ -0,0 +1,117 @@
+use super::*;
+
+const FULL: &str = r##"{
+  "game": {
+    "id": "game-00fe20da-94ad-11ea-bb37",
+    "ruleset": {
+      "name": "squad",
+      "version": "v1.2.3",
+      "settings": {
+        "foodSpawnChance": 15,
+        "minimumFood": 1,
+        "hazardDamagePerTurn": 14,
+        "hazardMap": "hz_spiral",
+        "hazardMapAuthor": "altersaddle",
+        "royale": { "shrinkEveryNTurns": 25 },
+        "squad": {
+          "allowBodyCollisions": true,
+          "sharedElimination": true,
+          "sharedHealth": false,
+          "sharedLength": true
+        }
+      }
+    },
+    "map": "standard",
+    "timeout": 500,
+    "source": "league"
+  },
+  "turn": 14,
+  "board": {
+    "height": 11,
+    "width": 11,
+    "food": [{ "x": 5, "y": 5 }],
+    "hazards": [{ "x": 0, "y": 0 }],
+    "snakes": [
+      {
+        "id": "snake-508e96ac-94ad-11ea-bb37",
+        "name": "My Snake",
+        "health": 54,
+        "body": [{ "x": 0, "y": 1 }, { "x": 0, "y": 2 }],
+        "latency": "111",
+        "head": { "x": 0, "y": 1 },
+        "length": 2,
+        "shout": "why are we shouting??",
+        "squad": "1",
+        "customizations": { "color": "#FF0000", "head": "pixel", "tail": "pixel" }
+      }
+    ]
+  },
+  "you": {
+    "id": "snake-508e96ac-94ad-11ea-bb37",
+    "name": "My Snake",
+    "health": 54,
+    "body": [{ "x": 0, "y": 1 }, { "x": 0, "y": 2 }],
+    "latency": "0",
+    "head": { "x": 0, "y": 1 },
+    "length": 2,
+    "shout": "",
+    "squad": "1",
+    "customizations": { "color": "#FF0000", "head": "pixel", "tail": "pixel" }
+  }
+}"##;
+
+#[test]
+fn parses_full_request() {
+    let req: MoveRequest = serde_json::from_str(FULL).unwrap();
+    assert_eq!(req.game.id, "game-00fe20da-94ad-11ea-bb37");
+    assert_eq!(req.game.ruleset.name, "squad");
+    assert_eq!(req.game.ruleset.settings.hazard_damage_per_turn, Some(14));
+    assert_eq!(req.game.ruleset.settings.royale.shrink_every_n_turns, 25);
+    assert!(req.game.ruleset.settings.squad.shared_length);
+    assert_eq!(req.game.map, "standard");
+    assert_eq!(req.game.source, "league");
+
+    let snake = &req.board.snakes[0];
+    assert_eq!(snake.name, "My Snake");
+    assert_eq!(snake.latency_ms(), Some(111));
+    assert_eq!(snake.squad, "1");
+    assert_eq!(snake.customizations.head, "pixel");
+    assert_eq!((snake.head.x, snake.head.y), (0, 1));
+    assert_eq!(req.you.latency_ms(), None);
+}
+
+#[test]
+fn parses_minimal_request() {
+    let req: MoveRequest = serde_json::from_str(include_str!("../../payload.json")).unwrap();
+    assert_eq!(req.game.ruleset.name, "standard");
+    assert_eq!(req.game.ruleset.settings.hazard_damage_per_turn, None);
+    assert_eq!(req.game.source, "");
+    assert_eq!(req.board.snakes[1].name, "DaBaby");
+    assert_eq!(req.board.snakes[1].latency_ms(), Some(243));
+    assert_eq!(req.you.squad, "");
+}
+
+#[test]
+fn parses_escaped_text() {
+    let json = FULL
+        .replace(r#""why are we shouting??""#, r#""say \"hi\"\\n \u003cb\u003e""#)
+        .replace(r#""name": "My Snake""#, r#""name": "Snake\tTwo""#);
+    let req: MoveRequest = serde_json::from_str(&json).unwrap();
+    let snake = &req.board.snakes[0];
+    assert_eq!(snake.shout, "say \"hi\"\\n <b>");
+    assert_eq!(snake.name, "Snake\tTwo");
+    assert!(matches!(snake.shout, Cow::Owned(_)));
+    assert!(matches!(req.game.id, Cow::Borrowed(_)));
+}
+
+#[test]
+fn parses_owned_requests() {
+    let json = FULL.replace(r#""why are we shouting??""#, r#""say \"hi\"""#);
+    let req = MoveRequest::parse_owned(json.as_bytes()).unwrap();
+    drop(json);
+    assert_eq!(req.board.snakes[0].shout, "say \"hi\"");
+    assert_eq!(req.game.id, "game-00fe20da-94ad-11ea-bb37");
+
+    let trailing = format!("{} {{}}", FULL);
+    assert!(MoveRequest::parse_owned(trailing.as_bytes()).is_err());
+}
@@ -0,0 +1,373 @@
+use super::api;
+use super::http;
+use super::snake;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// Environment variable naming the configuration file, like `--config`.
+const CONFIG_VAR: &str = "SNAKE_CONFIG";
+
+/// Everything configurable at startup. Each setting is taken from the
+/// defaults, a TOML file, the environment and the command line flags, a later
+/// source overriding an earlier one.
+///
+/// Settings are named after their section and field in the file, like
+/// `search.max_depth`. The environment variable is the field in capitals
+/// prefixed with `SNAKE_` and the flag the field with dashes, like
+/// `SNAKE_MAX_DEPTH` and `--max-depth`. Weights are prefixed with `WEIGHT_`
+/// and `weight-`, like `SNAKE_WEIGHT_EAT` and `--weight-eat`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: Server,
+    pub appearance: Appearance,
+    pub search: Search,
+    pub weights: snake::Weights,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Server {
+    pub listen: String,
+    /// Connections served at once.
+    pub workers: usize,
+    /// Largest request accepted, head and body together.
+    pub max_request_bytes: usize,
+    /// Share of the game timeout a move may take before the fallback move is
+    /// sent instead.
+    pub deadline_percent: u32,
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server {
+            listen: "0.0.0.0:3000".to_string(),
+            workers: 8,
+            max_request_bytes: http::DEFAULT_MAX_SIZE,
+            deadline_percent: 80,
+        }
+    }
+}
+
+/// How the snake looks, sent in answer to `GET /`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Appearance {
+    pub author: String,
+    pub color: String,
+    pub head: String,
+    pub tail: String,
+    pub version: String,
+}
+
+impl Appearance {
+    pub fn response(&self) -> api::RootResponse<'_> {
+        api::RootResponse {
+            api_version: "1",
+            author: &self.author,
+            color: &self.color,
+            head: &self.head,
+            tail: &self.tail,
+            version: &self.version,
+        }
+    }
+}
+
+impl Default for Appearance {
+    fn default() -> Appearance {
+        Appearance {
+            author: "colinjfw".to_string(),
+            color: "#FF69B4".to_string(),
+            head: "default".to_string(),
+            tail: "default".to_string(),
+            version: "v0.0.2".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Search {
+    /// Comma separated list of strategy names, optionally prefixed with the
+    /// ruleset they apply to, like `minimax,royale=greedy`.
+    pub strategy: String,
+    /// Time kept back from each timeout for the network.
+    pub margin_ms: u64,
+    /// Threads to search on, all cores when 0.
+    pub threads: usize,
+    pub max_depth: usize,
+    pub playout_depth: usize,
+    /// Health snakes eat back up to, for rules other than the standard ones.
+    pub max_health: u16,
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        let settings = snake::Settings::default();
+        Search {
+            strategy: "minimax".to_string(),
+            margin_ms: 50,
+            threads: 0,
+            max_depth: settings.max_depth,
+            playout_depth: settings.playout_depth,
+            max_health: settings.max_health,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration file given by `--config` or `SNAKE_CONFIG`, if
+    /// any, and applies the environment and flags on top. `env` looks up an
+    /// environment variable.
+    pub fn load<F>(args: &[String], env: F) -> Result<Config, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut flags = parse_flags(args)?;
+        let config = match flags.remove("config").or_else(|| env(CONFIG_VAR)) {
+            Some(path) => {
+                let content =
+                    fs::read_to_string(&path).map_err(|err| format!("{}: {}", path, err))?;
+                toml::from_str(&content).map_err(|err| format!("{}: {}", path, err))?
+            }
+            None => Config::default(),
+        };
+
+        let mut value = toml::Value::try_from(&config).map_err(|err| err.to_string())?;
+        for (section, fields) in sections_mut(&mut value) {
+            for (field, current) in fields.iter_mut() {
+                let (var, flag) = names(section, field);
+                if let Some(raw) = env(&var) {
+                    *current =
+                        parse_as(current, &raw).map_err(|err| format!("{}: {}", var, err))?;
+                }
+                if let Some(raw) = flags.remove(&flag) {
+                    *current =
+                        parse_as(current, &raw).map_err(|err| format!("--{}: {}", flag, err))?;
+                }
+            }
+        }
+        if let Some(flag) = flags.keys().next() {
+            return Err(format!("unknown flag --{}, see --help", flag));
+        }
+
+        let config: Config = value.try_into().map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.server
+            .listen
+            .parse::<SocketAddr>()
+            .map_err(|err| format!("server.listen: {}: {:?}", err, self.server.listen))?;
+        if self.server.workers == 0 {
+            return Err("server.workers: at least one worker is needed".into());
+        }
+        if self.server.max_request_bytes < 1024 {
+            return Err("server.max_request_bytes: must be at least 1024".into());
+        }
+        if !(1..=100).contains(&self.server.deadline_percent) {
+            return Err("server.deadline_percent: must be between 1 and 100".into());
+        }
+        let color = self.appearance.color.strip_prefix('#').unwrap_or_default();
+        if color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "appearance.color: expected a color like #FF69B4, got {:?}",
+                self.appearance.color
+            ));
+        }
+        if self.search.max_depth == 0 || self.search.playout_depth == 0 {
+            return Err("search.max_depth and search.playout_depth must be at least 1".into());
+        }
+        if !(1..=255).contains(&self.search.max_health) {
+            return Err("search.max_health: must be between 1 and 255".into());
+        }
+        let weights = toml::Value::try_from(self.weights).map_err(|err| err.to_string())?;
+        for (field, weight) in weights.as_table().into_iter().flatten() {
+            if weight.as_float().is_some_and(|weight| !weight.is_finite()) {
+                return Err(format!("weights.{}: must be a finite number", field));
+            }
+        }
+        // Strategy names are checked by the runner.
+        self.runner().map(|_| ())
+    }
+
+    /// A runner searching with the configured strategies, limits and weights.
+    pub fn runner(&self) -> Result<snake::Runner, String> {
+        let mut runner = snake::Runner::new();
+        runner.set_settings(snake::Settings {
+            max_depth: self.search.max_depth,
+            playout_depth: self.search.playout_depth,
+            max_health: self.search.max_health,
+            weights: self.weights,
+        });
+        let entries = self.search.strategy.split(',').map(str::trim);
+        for entry in entries.filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((ruleset, name)) => runner.set_strategy(Some(ruleset.trim()), name.trim()),
+                None => runner.set_strategy(None, entry),
+            }
+            .map_err(|err| format!("search.strategy: {}", err))?;
+        }
+        runner.set_margin(Duration::from_millis(self.search.margin_ms));
+        runner.set_deadline_percent(self.server.deadline_percent);
+        if self.search.threads > 0 {
+            runner.set_threads(self.search.threads);
+        }
+        Ok(runner)
+    }
+}
+
+/// Lists every setting with its variable, flag and default.
+pub fn usage() -> String {
+    let mut usage = format!(
+        "usage: snake [--config FILE] [--FLAG VALUE]...\n       \
+         snake replay FILE [--diff] [--tree-depth N] [--FLAG VALUE]...\n       \
+         snake simulate PLAYER... [--seed N] [--log FILE] [--FLAG VALUE]...\n       \
+         snake tournament VARIANT... [--games N] [--report FILE] [--FLAG VALUE]...\n\n\
+         Settings are read from the TOML file given by --config or {}, then\n\
+         from the environment, then from the flags.\n\n",
+        CONFIG_VAR
+    );
+    let mut value = toml::Value::try_from(Config::default()).unwrap();
+    for (section, fields) in sections_mut(&mut value) {
+        for (field, default) in fields.iter() {
+            let (var, flag) = names(section, field);
+            writeln!(
+                usage,
+                "  {:<28} {:<28} --{:<24} {}",
+                format!("{}.{}", section, field),
+                var,
+                flag,
+                default
+            )
+            .unwrap();
+        }
+    }
+    usage
+}
+
+/// The arguments of a subcommand once its own flags are taken out.
+pub struct Rest {
+    /// Arguments that are not flags, in order.
+    pub positional: Vec<String>,
+    /// Flags the subcommand does not take, as they came, for `Config::load`.
+    pub config: Vec<String>,
+}
+
+/// Hands every flag in `args`, given as `--name value` or `--name=value`, to
+/// `take` by name with its value. Flags named in `switches` have no value
+/// unless given with `=`. `take` returns false for the flags it leaves to the
+/// configuration.
+pub fn split_flags<F>(args: &[String], switches: &[&str], mut take: F) -> Result<Rest, String>
+where
+    F: FnMut(&str, Option<&str>) -> Result<bool, String>,
+{
+    let mut rest = Rest {
+        positional: Vec::new(),
+        config: Vec::new(),
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = match arg.strip_prefix("--") {
+            Some(flag) => flag,
+            None => {
+                rest.positional.push(arg.clone());
+                continue;
+            }
+        };
+        let (name, value, next) = match flag.split_once('=') {
+            Some((name, value)) => (name, Some(value), None),
+            None if switches.contains(&flag) => (flag, None, None),
+            None => {
+                let next = args.next();
+                (flag, next.map(String::as_str), next)
+            }
+        };
+        if !take(name, value)? {
+            rest.config.push(arg.clone());
+            rest.config.extend(next.cloned());
+        }
+    }
+    Ok(rest)
+}
+
+/// The value of the flag `name`.
+pub fn flag_value<T>(name: &str, value: Option<&str>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("--{}: missing value", name))?;
+    value
+        .parse()
+        .map_err(|err| format!("--{}: {}: {:?}", name, err, value))
+}
+
+/// Flags as `--name value` or `--name=value`, by name.
+fn parse_flags(args: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut flags = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let flag = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("unexpected argument {:?}, see --help", arg))?;
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, value.to_string()),
+            None => match args.next() {
+                Some(value) => (flag, value.clone()),
+                None => return Err(format!("--{}: missing value", flag)),
+            },
+        };
+        flags.insert(name.to_string(), value);
+    }
+    Ok(flags)
+}
+
+fn sections_mut(
+    value: &mut toml::Value,
+) -> impl Iterator<Item = (&String, &mut toml::value::Table)> {
+    value
+        .as_table_mut()
+        .into_iter()
+        .flatten()
+        .filter_map(|(section, fields)| Some((section, fields.as_table_mut()?)))
+}
+
+/// Environment variable and flag of a setting.
+fn names(section: &str, field: &str) -> (String, String) {
+    let field = match section {
+        "weights" => format!("weight_{}", field),
+        _ => field.to_string(),
+    };
+    (
+        format!("SNAKE_{}", field.to_uppercase()),
+        field.replace('_', "-"),
+    )
+}
+
+/// Parses a raw override as the same type as the value it replaces.
+fn parse_as(current: &toml::Value, raw: &str) -> Result<toml::Value, String> {
+    let raw = raw.trim();
+    Ok(match current {
+        toml::Value::Integer(_) => {
+            toml::Value::Integer(raw.parse().map_err(|err| format!("{}: {:?}", err, raw))?)
+        }
+        toml::Value::Float(_) => {
+            toml::Value::Float(raw.parse().map_err(|err| format!("{}: {:?}", err, raw))?)
+        }
+        toml::Value::Boolean(_) => {
+            toml::Value::Boolean(raw.parse().map_err(|err| format!("{}: {:?}", err, raw))?)
+        }
+        _ => toml::Value::String(raw.to_string()),
+    })
+}
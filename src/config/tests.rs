@@ -0,0 +1,116 @@
+use super::super::testing::{self, args, TempFile};
+use super::*;
+
+fn env_of<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    move |var| {
+        vars.iter()
+            .find(|(name, _)| *name == var)
+            .map(|(_, value)| value.to_string())
+    }
+}
+
+#[test]
+fn loads_defaults() {
+    let config = Config::load(&[], env_of(&[])).unwrap();
+    assert_eq!(config, Config::default());
+    assert_eq!(config.search.max_depth, 20);
+    assert_eq!(config.weights.eat, -4.0);
+    assert!(usage().contains("SNAKE_WEIGHT_EAT"));
+}
+
+#[test]
+fn flags_override_environment_over_file() {
+    let file = TempFile::with(
+        "config",
+        "[search]\nmax_depth = 8\nstrategy = \"greedy\"\n\n[weights]\neat = -2\nfood = -1.5\n",
+    );
+    let path = file.path();
+
+    let vars = [
+        ("SNAKE_CONFIG", path),
+        ("SNAKE_MAX_DEPTH", "10"),
+        ("SNAKE_WEIGHT_EAT", "-3"),
+        ("SNAKE_COLOR", "#00aa00"),
+    ];
+    let config = Config::load(
+        &args(&["--max-depth", "12", "--listen=127.0.0.1:8080"]),
+        env_of(&vars),
+    )
+    .unwrap();
+    assert_eq!(config.search.max_depth, 12);
+    assert_eq!(config.search.strategy, "greedy");
+    assert_eq!(config.weights.eat, -3.0);
+    assert_eq!(config.weights.food, -1.5);
+    assert_eq!(config.appearance.color, "#00aa00");
+    assert_eq!(config.server.listen, "127.0.0.1:8080");
+
+    // The flag wins over the environment for the file too.
+    let config = Config::load(&args(&["--config", "/nonexistent.toml"]), env_of(&vars));
+    assert!(config.unwrap_err().starts_with("/nonexistent.toml: "));
+}
+
+#[test]
+fn splits_subcommand_flags() {
+    let mut taken = Vec::new();
+    let rest = split_flags(
+        &args(&[
+            "a",
+            "--diff",
+            "b",
+            "--seed",
+            "3",
+            "--threads=2",
+            "--max-depth",
+            "4",
+            "--log=x",
+        ]),
+        &["diff"],
+        |name, value| {
+            taken.push((name.to_string(), value.map(str::to_string)));
+            Ok(!matches!(name, "threads" | "max-depth"))
+        },
+    )
+    .unwrap();
+    assert_eq!(rest.positional, args(&["a", "b"]));
+    assert_eq!(rest.config, args(&["--threads=2", "--max-depth", "4"]));
+    assert_eq!(taken[0], ("diff".to_string(), None));
+    assert_eq!(taken[1], ("seed".to_string(), Some("3".to_string())));
+    assert_eq!(taken[4], ("log".to_string(), Some("x".to_string())));
+
+    let err = split_flags(&args(&["--seed"]), &[], |name, value| {
+        flag_value::<u64>(name, value).map(|_| true)
+    });
+    assert_eq!(err.err(), Some("--seed: missing value".to_string()));
+}
+
+#[test]
+fn rejects_invalid_settings() {
+    let flags: &[(&[&str], &str)] = &[
+        (&["--max-depht", "3"], "unknown flag --max-depht"),
+        (&["--threads"], "--threads: missing value"),
+        (&["fast"], "unexpected argument"),
+        (&["--workers", "0"], "server.workers"),
+        (&["--listen", "3000"], "server.listen"),
+        (&["--color", "pink"], "appearance.color"),
+        (
+            &["--strategy", "minimax,royale=best"],
+            "unknown strategy \"best\"",
+        ),
+        (&["--weight-eat", "nan"], "weights.eat"),
+        (&["--deadline-percent", "150"], "server.deadline_percent"),
+        (&["--max-health", "0"], "search.max_health"),
+    ];
+    testing::assert_rejects(flags, |flags| Config::load(flags, env_of(&[])));
+
+    let vars = [
+        ("SNAKE_WORKERS", "many", "SNAKE_WORKERS: invalid digit"),
+        ("SNAKE_WORKERS", "-1", "server.workers"),
+    ];
+    for (var, value, expected) in vars {
+        let err = Config::load(&[], env_of(&[(var, value)])).unwrap_err();
+        assert!(err.contains(expected), "{}={}: {}", var, value, err);
+    }
+
+    let err = toml::from_str::<Config>("[search]\ndepth = 3\n").unwrap_err();
+    assert!(err.to_string().contains("unknown field `depth`"), "{}", err);
+}
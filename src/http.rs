@@ -0,0 +1,388 @@
+use httparse::EMPTY_HEADER;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::str;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// Largest request accepted, head and body together, when nothing is
+/// configured. A 25x25 board with a full field of snakes is well below.
+pub const DEFAULT_MAX_SIZE: usize = 1 << 20;
+
+const INITIAL_SIZE: usize = 12288;
+const MAX_HEADERS: usize = 32;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The client closed the connection between two requests.
+    Closed,
+    Io(io::Error),
+    Malformed(String),
+    TooLarge,
+}
+
+impl Error {
+    /// Status to answer with when the request could not be read, `None` when
+    /// the connection is gone or unusable.
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            Error::Malformed(_) => Some(Status::BadRequest),
+            Error::TooLarge => Some(Status::PayloadTooLarge),
+            Error::Closed | Error::Io(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<httparse::Error> for Error {
+    fn from(err: httparse::Error) -> Error {
+        Error::Malformed(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Malformed(err.to_string())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Closed => write!(f, "connection closed"),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Malformed(err) => write!(f, "malformed request: {}", err),
+            Error::TooLarge => write!(f, "request too large"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    BadRequest,
+    NotFound,
+    /// With the method the path accepts.
+    MethodNotAllowed(&'static str),
+    PayloadTooLarge,
+    InternalServerError,
+}
+
+impl Status {
+    pub fn code(self) -> u16 {
+        match self {
+            Status::Ok => 200,
+            Status::BadRequest => 400,
+            Status::NotFound => 404,
+            Status::MethodNotAllowed(_) => 405,
+            Status::PayloadTooLarge => 413,
+            Status::InternalServerError => 500,
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Status::Ok => "OK",
+            Status::BadRequest => "Bad Request",
+            Status::NotFound => "Not Found",
+            Status::MethodNotAllowed(_) => "Method Not Allowed",
+            Status::PayloadTooLarge => "Payload Too Large",
+            Status::InternalServerError => "Internal Server Error",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.reason())
+    }
+}
+
+/// Writes the head of a response with a body of `len` bytes.
+pub fn write_head<W: Write>(
+    w: &mut W,
+    status: Status,
+    content_type: &str,
+    len: usize,
+    keep_alive: bool,
+) -> io::Result<()> {
+    write!(
+        w,
+        "HTTP/1.1 {}\r\n\
+         content-type: {}\r\n\
+         content-length: {}\r\n\
+         connection: {}\r\n",
+        status,
+        content_type,
+        len,
+        if keep_alive { "keep-alive" } else { "close" },
+    )?;
+    if let Status::MethodNotAllowed(allow) = status {
+        write!(w, "allow: {}\r\n", allow)?;
+    }
+    write!(w, "\r\n")
+}
+
+/// Posts a JSON body to `path` on the server at `addr` and returns the status
+/// code and body of the response, for talking to other snakes. Each request
+/// takes a connection of its own, closed once the response is read.
+pub fn post(addr: &str, path: &str, body: &[u8], timeout: Duration) -> io::Result<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\n\
+         host: {}\r\n\
+         content-type: application/json\r\n\
+         content-length: {}\r\n\
+         connection: close\r\n\r\n",
+        path,
+        addr,
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    parse_response(&response)
+}
+
+/// Splits a whole response into its status code and body.
+fn parse_response(response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let malformed = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+    let mut headers = [EMPTY_HEADER; MAX_HEADERS];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let head_len = match parsed.parse(response).map_err(|err| malformed(err.to_string()))? {
+        httparse::Status::Complete(len) => len,
+        httparse::Status::Partial => return Err(malformed("truncated response".into())),
+    };
+    let body = &response[head_len..];
+    let len = parsed
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-length"))
+        .map(|header| {
+            str::from_utf8(header.value)
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .ok_or_else(|| malformed("invalid content-length".into()))
+        })
+        .transpose()?
+        .unwrap_or(body.len());
+    if len > body.len() {
+        return Err(malformed("truncated response".into()));
+    }
+    Ok((parsed.code.unwrap_or_default(), body[..len].to_vec()))
+}
+
+/// A request read off a connection, borrowed from its reader.
+#[derive(Debug)]
+pub struct Request<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub keep_alive: bool,
+    pub body: &'a [u8],
+}
+
+enum Framing {
+    Length(usize),
+    Chunked,
+}
+
+/// Reads requests off a connection. Bytes read past the end of a request are
+/// kept for the next one, the buffer only grows up to the maximum size.
+pub struct Reader {
+    buf: Vec<u8>,
+    len: usize,
+    method: String,
+    path: String,
+    body: Vec<u8>,
+    max_size: usize,
+}
+
+impl Reader {
+    pub fn new(max_size: usize) -> Reader {
+        Reader {
+            buf: Vec::new(),
+            len: 0,
+            method: String::new(),
+            path: String::new(),
+            body: Vec::new(),
+            max_size,
+        }
+    }
+
+    /// Forgets anything buffered from a previous connection.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn read<R: Read>(&mut self, stream: &mut R) -> Result<Request<'_>, Error> {
+        let (head_len, framing, keep_alive) = loop {
+            if let Some(head) = self.parse_head()? {
+                break head;
+            }
+            self.fill(stream)?;
+        };
+
+        self.body.clear();
+        let end = match framing {
+            Framing::Length(len) => {
+                let end = head_len.checked_add(len).ok_or(Error::TooLarge)?;
+                if end > self.max_size {
+                    return Err(Error::TooLarge);
+                }
+                while self.len < end {
+                    self.fill(stream)?;
+                }
+                self.body.extend_from_slice(&self.buf[head_len..end]);
+                end
+            }
+            Framing::Chunked => self.read_chunks(stream, head_len)?,
+        };
+        self.buf.copy_within(end..self.len, 0);
+        self.len -= end;
+
+        Ok(Request {
+            method: &self.method,
+            path: &self.path,
+            keep_alive,
+            body: &self.body,
+        })
+    }
+
+    /// Parses the head once it is all buffered, returning its length, how
+    /// the body is framed and whether the connection stays open.
+    fn parse_head(&mut self) -> Result<Option<(usize, Framing, bool)>, Error> {
+        let mut headers = [EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        let head_len = match request.parse(&self.buf[..self.len])? {
+            httparse::Status::Complete(len) => len,
+            httparse::Status::Partial => return Ok(None),
+        };
+
+        self.method.clear();
+        self.method.push_str(request.method.unwrap_or_default());
+        self.path.clear();
+        self.path.push_str(request.path.unwrap_or_default());
+
+        let mut framing = Framing::Length(0);
+        let mut connection = None;
+        for header in request.headers.iter() {
+            if header.name.eq_ignore_ascii_case("content-length") {
+                if matches!(framing, Framing::Length(_)) {
+                    framing = Framing::Length(
+                        str::from_utf8(header.value)
+                            .ok()
+                            .and_then(|value| value.trim().parse().ok())
+                            .ok_or_else(|| Error::Malformed("invalid content-length".into()))?,
+                    );
+                }
+            } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+                // Chunked has to be the last coding and wins over a length.
+                let last = header
+                    .value
+                    .rsplit(|b| *b == b',')
+                    .next()
+                    .unwrap_or_default();
+                if !last.trim_ascii().eq_ignore_ascii_case(b"chunked") {
+                    return Err(Error::Malformed("unsupported transfer-encoding".into()));
+                }
+                framing = Framing::Chunked;
+            } else if header.name.eq_ignore_ascii_case("connection") {
+                connection = Some(header.value);
+            }
+        }
+        Ok(Some((
+            head_len,
+            framing,
+            keep_alive(request.version, connection),
+        )))
+    }
+
+    /// Decodes a chunked body starting at `pos`, returning where the request
+    /// ends. Trailers are skipped.
+    fn read_chunks<R: Read>(&mut self, stream: &mut R, mut pos: usize) -> Result<usize, Error> {
+        loop {
+            let (line, size) = match httparse::parse_chunk_size(&self.buf[pos..self.len]) {
+                Ok(httparse::Status::Complete(chunk)) => chunk,
+                Ok(httparse::Status::Partial) => {
+                    self.fill(stream)?;
+                    continue;
+                }
+                Err(_) => return Err(Error::Malformed("invalid chunk size".into())),
+            };
+            pos += line;
+            if size == 0 {
+                break;
+            }
+            let size = usize::try_from(size).map_err(|_| Error::TooLarge)?;
+            let end = pos.checked_add(size).ok_or(Error::TooLarge)?;
+            if end > self.max_size {
+                return Err(Error::TooLarge);
+            }
+            while self.len < end + 2 {
+                self.fill(stream)?;
+            }
+            if &self.buf[end..end + 2] != b"\r\n" {
+                return Err(Error::Malformed("chunk not terminated".into()));
+            }
+            self.body.extend_from_slice(&self.buf[pos..end]);
+            pos = end + 2;
+        }
+
+        loop {
+            match self.buf[pos..self.len]
+                .windows(2)
+                .position(|w| w == b"\r\n")
+            {
+                Some(0) => return Ok(pos + 2),
+                Some(line) => pos += line + 2,
+                None => self.fill(stream)?,
+            }
+        }
+    }
+
+    /// Reads more of the request, growing the buffer when full.
+    fn fill<R: Read>(&mut self, stream: &mut R) -> Result<(), Error> {
+        if self.len == self.buf.len() {
+            if self.len >= self.max_size {
+                return Err(Error::TooLarge);
+            }
+            let size = (self.buf.len() * 2).max(INITIAL_SIZE).min(self.max_size);
+            self.buf.resize(size, 0);
+        }
+        loop {
+            match stream.read(&mut self.buf[self.len..]) {
+                Ok(0) if self.len == 0 => return Err(Error::Closed),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(read) => {
+                    self.len += read;
+                    return Ok(());
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// HTTP/1.1 connections stay open unless the client asks to close them,
+/// HTTP/1.0 ones only when it asks to keep them.
+fn keep_alive(version: Option<u8>, connection: Option<&[u8]>) -> bool {
+    match (version, connection) {
+        (_, Some(value)) if value.eq_ignore_ascii_case(b"close") => false,
+        (_, Some(value)) if value.eq_ignore_ascii_case(b"keep-alive") => true,
+        (Some(1), _) => true,
+        _ => false,
+    }
+}
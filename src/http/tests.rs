@@ -0,0 +1,206 @@
+use super::*;
+
+const PAYLOAD: &str = include_str!("../../payload.json");
+
+/// Hands out the data a few bytes per read, like a slow connection.
+struct Trickle<'a> {
+    data: &'a [u8],
+    step: usize,
+}
+
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.step.min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+fn post(body: &str) -> String {
+    format!(
+        "POST /move HTTP/1.1\r\nHost: snake\r\nCONTENT-length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn reads_across_partial_reads() {
+    let raw = post(PAYLOAD);
+    let mut stream = Trickle {
+        data: raw.as_bytes(),
+        step: 7,
+    };
+    let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+    let request = reader.read(&mut stream).unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/move");
+    assert!(request.keep_alive);
+    assert_eq!(request.body, PAYLOAD.as_bytes());
+    assert!(matches!(reader.read(&mut stream), Err(Error::Closed)));
+}
+
+#[test]
+fn reads_bodies_larger_than_the_initial_buffer() {
+    let body = "x".repeat(INITIAL_SIZE * 5);
+    let raw = post(&body);
+    let mut stream = Trickle {
+        data: raw.as_bytes(),
+        step: 4096,
+    };
+    let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+    assert_eq!(reader.read(&mut stream).unwrap().body, body.as_bytes());
+}
+
+#[test]
+fn keeps_pipelined_requests() {
+    let raw = format!(
+        "{}{}",
+        post("{\"a\":1}"),
+        "GET / HTTP/1.1\r\nConnection: close\r\n\r\n"
+    );
+    let mut stream = Trickle {
+        data: raw.as_bytes(),
+        step: raw.len(),
+    };
+    let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+    assert_eq!(reader.read(&mut stream).unwrap().body, b"{\"a\":1}");
+    let request = reader.read(&mut stream).unwrap();
+    assert_eq!(request.path, "/");
+    assert!(!request.keep_alive);
+    assert!(request.body.is_empty());
+}
+
+#[test]
+fn decodes_chunked_bodies() {
+    let raw = "POST /end HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+               4\r\n{\"a\"\r\n\
+               3;ext=1\r\n:1}\r\n\
+               0\r\nTrailer: yes\r\n\r\n\
+               GET / HTTP/1.0\r\n\r\n";
+    let mut stream = Trickle {
+        data: raw.as_bytes(),
+        step: 3,
+    };
+    let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+    assert_eq!(reader.read(&mut stream).unwrap().body, b"{\"a\":1}");
+    // HTTP/1.0 closes unless asked otherwise.
+    assert!(!reader.read(&mut stream).unwrap().keep_alive);
+}
+
+#[test]
+fn rejects_oversized_requests() {
+    let body = "x".repeat(4096);
+    let raw = post(&body);
+    let mut reader = Reader::new(1024);
+    let mut stream = Trickle {
+        data: raw.as_bytes(),
+        step: 512,
+    };
+    assert!(matches!(reader.read(&mut stream), Err(Error::TooLarge)));
+
+    let chunked = format!(
+        "POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n1000\r\n{}\r\n0\r\n\r\n",
+        body
+    );
+    let mut reader = Reader::new(1024);
+    let mut stream = Trickle {
+        data: chunked.as_bytes(),
+        step: 512,
+    };
+    assert!(matches!(reader.read(&mut stream), Err(Error::TooLarge)));
+
+    // A length that overflows once the head is added.
+    let huge = format!("POST / HTTP/1.1\r\ncontent-length: {}\r\n\r\n", usize::MAX);
+    let mut reader = Reader::new(1024);
+    let mut stream = Trickle {
+        data: huge.as_bytes(),
+        step: 512,
+    };
+    assert!(matches!(reader.read(&mut stream), Err(Error::TooLarge)));
+}
+
+#[test]
+fn rejects_truncated_and_malformed_requests() {
+    let raw = post(PAYLOAD);
+    let mut stream = Trickle {
+        data: &raw.as_bytes()[..raw.len() - 10],
+        step: 100,
+    };
+    let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+    assert!(matches!(
+        reader.read(&mut stream),
+        Err(Error::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof
+    ));
+
+    for raw in [
+        "POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\n",
+        "POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+        "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+        "NOT HTTP\r\n\r\n",
+    ] {
+        let mut stream = Trickle {
+            data: raw.as_bytes(),
+            step: raw.len(),
+        };
+        let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+        assert!(
+            matches!(reader.read(&mut stream), Err(Error::Malformed(_))),
+            "{:?}",
+            raw
+        );
+    }
+}
+
+#[test]
+fn writes_response_heads() {
+    let mut head = Vec::new();
+    write_head(
+        &mut head,
+        Status::MethodNotAllowed("POST"),
+        "application/json",
+        12,
+        true,
+    ).unwrap();
+    assert_eq!(
+        String::from_utf8(head).unwrap(),
+        "HTTP/1.1 405 Method Not Allowed\r\n\
+         content-type: application/json\r\n\
+         content-length: 12\r\n\
+         connection: keep-alive\r\n\
+         allow: POST\r\n\r\n"
+    );
+
+    assert_eq!(
+        Error::Malformed("bad".into()).status(),
+        Some(Status::BadRequest)
+    );
+    assert_eq!(Error::TooLarge.status(), Some(Status::PayloadTooLarge));
+    assert_eq!(Error::Closed.status(), None);
+}
+
+#[test]
+fn posts_to_a_server() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = Reader::new(DEFAULT_MAX_SIZE);
+        let request = reader.read(&mut stream).unwrap();
+        assert_eq!((request.method, request.path), ("POST", "/move"));
+        assert!(!request.keep_alive);
+        let body = request.body.to_vec();
+        write_head(&mut stream, Status::Ok, "application/json", body.len(), false).unwrap();
+        stream.write_all(&body).unwrap();
+    });
+    let (code, body) = super::post(&addr, "/move", b"{\"a\":1}", Duration::from_secs(5)).unwrap();
+    server.join().unwrap();
+    assert_eq!(code, 200);
+    assert_eq!(body, b"{\"a\":1}");
+
+    let truncated = "HTTP/1.1 200 OK\r\ncontent-length: 10\r\n\r\n{}";
+    assert!(parse_response(truncated.as_bytes()).is_err());
+    let (code, body) = parse_response(b"HTTP/1.0 404 Not Found\r\n\r\nnope").unwrap();
+    assert_eq!((code, body.as_slice()), (404, &b"nope"[..]));
+}
@@ -1,115 +1,493 @@
-#![feature(test)]
-#![feature(once_cell)]
+#![cfg_attr(test, feature(test))]
 
 mod alloc;
 mod api;
+mod config;
+mod http;
+mod replay;
+mod simulate;
 mod snake;
 mod stats;
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod tests;
+mod tournament;
 
-use httparse::{Request, EMPTY_HEADER};
+use config::Config;
 use log::*;
-use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::io::{self, ErrorKind, Write};
 use std::net::{TcpListener, TcpStream};
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
 use std::str;
-use std::time::Instant;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-const SNAKE: api::RootResponse = api::RootResponse {
-    api_version: "1",
-    author: "colinjfw",
-    color: "#FF69B4",
-    head: "default",
-    tail: "default",
-    version: "v0.0.2",
-};
+/// Idle connections are closed after this long without a request.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time `/start` and `/end` may take.
+const DEADLINE: Duration = Duration::from_secs(1);
+const JSON: &str = "application/json";
+/// Prometheus text format.
+const METRICS: &str = "text/plain; version=0.0.4";
 
-const HTTP_OK: &[u8] = "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\r\n".as_bytes();
+/// A request for the search thread, parsed once by the handler and shared
+/// with it, to fall back on if the search is given up on.
+enum Job {
+    Start(Arc<api::MoveRequest<'static>>),
+    End(Arc<api::MoveRequest<'static>>),
+    Move(Arc<api::MoveRequest<'static>>),
+}
+
+type Answer = Option<(api::Direction, snake::Diagnostics)>;
+
+impl Job {
+    fn run(&self, runner: &mut snake::Runner) -> Answer {
+        match self {
+            Job::Start(req) => runner.start(req),
+            Job::End(req) => runner.end(req),
+            Job::Move(req) => return Some(runner.run(req)),
+        }
+        None
+    }
+}
+
+enum Outcome {
+    Done(Option<api::Direction>),
+    Panicked,
+    Overrun,
+}
+
+/// Runs the runner on a thread of its own, so a job that panics or runs past
+/// its deadline can be given up on and answered some other way. A thread
+/// whose job is given up on is left to finish it alone, the next job gets a
+/// new thread rather than waiting behind it.
+struct Searcher {
+    /// Runner the threads are started with.
+    runner: snake::Runner,
+    name: String,
+    thread: Option<SearchThread>,
+    /// How the last move was searched, until taken for the request log.
+    diagnostics: Option<snake::Diagnostics>,
+}
+
+/// The channels to a search thread, dropped to let it go once its job is
+/// given up on.
+struct SearchThread {
+    jobs: mpsc::Sender<Job>,
+    results: mpsc::Receiver<(thread::Result<Answer>, alloc::Usage)>,
+}
+
+impl Searcher {
+    fn new(runner: snake::Runner, name: String) -> Searcher {
+        let mut searcher = Searcher {
+            runner,
+            name,
+            thread: None,
+            diagnostics: None,
+        };
+        searcher.thread = Some(searcher.spawn());
+        searcher
+    }
+
+    fn spawn(&self) -> SearchThread {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (done, results) = mpsc::channel();
+        let mut runner = self.runner.worker();
+        thread::Builder::new()
+            .name(self.name.clone())
+            .spawn(move || {
+                for job in queue {
+                    let allocations = alloc::Scope::start();
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| job.run(&mut runner)));
+                    let usage = allocations.usage();
+                    if let Err(mpsc::SendError((_, usage))) = done.send((result, usage)) {
+                        // Given up on, the request was answered and accounted
+                        // for without what the search allocated since.
+                        stats::STATS.search_late.inc();
+                        stats::STATS.search_late_alloc_bytes.inc_by(usage.bytes);
+                        return;
+                    }
+                }
+            })
+            .unwrap();
+        SearchThread { jobs, results }
+    }
+
+    fn run(&mut self, job: Job, deadline: Duration) -> Outcome {
+        if self.thread.is_none() {
+            self.thread = Some(self.spawn());
+        }
+        let thread = self.thread.as_ref().unwrap();
+        if thread.jobs.send(job).is_err() {
+            self.thread = None;
+            return Outcome::Panicked;
+        }
+        match thread.results.recv_timeout(deadline) {
+            Ok((result, usage)) => {
+                // The search thread allocates on behalf of the request.
+                alloc::absorb(usage);
+                match result {
+                    Ok(answer) => {
+                        let (direction, diagnostics) = answer.unzip();
+                        self.diagnostics = diagnostics;
+                        Outcome::Done(direction)
+                    }
+                    Err(_) => Outcome::Panicked,
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.thread = None;
+                Outcome::Overrun
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                self.thread = None;
+                Outcome::Panicked
+            }
+        }
+    }
+}
 
 struct Handler {
+    reader: http::Reader,
     response_buf: Vec<u8>,
-    request_buf: Vec<u8>,
-    runner: snake::Runner,
+    body_buf: Vec<u8>,
+    searcher: Searcher,
+    config: Arc<Config>,
+    /// Connections accepted and not yet taken by a worker.
+    waiting: Arc<AtomicUsize>,
 }
 
 impl Handler {
-    fn handle(&mut self, stream: &mut TcpStream) -> Result<()> {
-        let mut request_headers = [EMPTY_HEADER; 16];
-        let mut request = Request::new(&mut request_headers);
-
-        self.request_buf.resize(12288, 0);
-        stream.read(&mut self.request_buf)?;
-        let status = match request.parse(&self.request_buf) {
-            Ok(s) => s,
-            Err(e) => return Result::Err(Error::new(ErrorKind::Other, e)),
-        };
-        if status.is_partial() {
-            return Result::Err(Error::from(ErrorKind::InvalidInput));
+    fn new(
+        runner: snake::Runner,
+        config: Arc<Config>,
+        waiting: Arc<AtomicUsize>,
+        name: &str,
+    ) -> Handler {
+        Handler {
+            reader: http::Reader::new(config.server.max_request_bytes),
+            response_buf: Vec::with_capacity(12288),
+            body_buf: Vec::with_capacity(12288),
+            searcher: Searcher::new(runner, format!("{}-search", name)),
+            config,
+            waiting,
         }
-        let content_start = status.unwrap();
-        let content_len = match request
-            .headers
-            .iter()
-            .find(|header| header.name == "Content-Length")
-            .and_then(|header| str::from_utf8(header.value).ok())
-            .and_then(|value| value.parse::<usize>().ok())
+    }
+
+    /// Serves requests on the connection until the client closes it, asks
+    /// for it to be closed or stays idle past the read timeout. A connection
+    /// kept open holds the worker, it is closed after the response when other
+    /// connections wait for one.
+    fn serve(&mut self, mut stream: TcpStream) {
+        if let Err(err) = stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
         {
-            Some(len) => len,
-            None => 0,
-        };
-        let content = &self.request_buf[content_start..(content_start + content_len)];
-
-        self.response_buf.truncate(0);
-        self.response_buf.write(HTTP_OK)?;
-
-        if let Some(path) = request.path {
-            match path {
-                "/move" => {
-                    let req: api::MoveRequest = serde_json::from_slice(content)?;
-                    info!("/move request: {}", str::from_utf8(content).unwrap());
-                    let direction = self.runner.run(&req);
-                    serde_json::to_writer(
-                        &mut self.response_buf,
-                        &api::MoveResponse { direction },
-                    )?;
+            error!("http: connection setup failed {}", err);
+            return;
+        }
+        self.reader.reset();
+        loop {
+            let now = Instant::now();
+            match self.handle(&mut stream) {
+                Ok((status, keep_alive, usage)) => {
+                    match self.searcher.diagnostics.take() {
+                        Some(search) => info!(
+                            "request {}: in {:?}, search: {}, allocated: {}",
+                            status,
+                            now.elapsed(),
+                            search,
+                            usage
+                        ),
+                        None => info!(
+                            "request {}: in {:?}, allocated: {}",
+                            status,
+                            now.elapsed(),
+                            usage
+                        ),
+                    }
+                    if !keep_alive {
+                        return;
+                    }
+                }
+                Err(http::Error::Closed) => return,
+                Err(http::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    debug!("http: closing idle connection");
+                    return;
+                }
+                Err(err) => {
+                    error!("request failed: {}", err);
+                    return;
                 }
-                _ => {
-                    serde_json::to_writer(&mut self.response_buf, &SNAKE)?;
+            }
+        }
+    }
+
+    /// Handles one request, returning the status answered with, whether the
+    /// connection stays open and the memory allocated for it.
+    fn handle(
+        &mut self,
+        stream: &mut TcpStream,
+    ) -> Result<(http::Status, bool, alloc::Usage), http::Error> {
+        let read = self.reader.read(stream);
+        // Allocations count from the request on, not while waiting for it.
+        let allocations = alloc::Scope::start();
+        self.body_buf.clear();
+        let (status, content_type, keep_alive) = match read {
+            Ok(request) => {
+                // Time spent waiting for the request is not the server's.
+                let start = Instant::now();
+                debug!("{} {}", request.method, request.path);
+                let (status, content_type) = route(
+                    &mut self.searcher,
+                    &self.config,
+                    &request,
+                    &mut self.body_buf,
+                )?;
+                stats::STATS.requests.inc(request.path, status.code());
+                if request.path == "/move" {
+                    let usage = allocations.usage();
+                    stats::STATS
+                        .move_latency
+                        .observe(start.elapsed().as_secs_f64());
+                    stats::STATS.move_alloc_bytes.observe(usage.bytes as f64);
+                    stats::STATS.move_peak_bytes.observe(usage.peak as f64);
+                }
+                let waiting = self.waiting.load(Ordering::Relaxed);
+                if request.keep_alive && waiting > 0 {
+                    debug!("http: closing connection, {} waiting", waiting);
                 }
-            };
+                (status, content_type, request.keep_alive && waiting == 0)
+            }
+            // Whatever follows on the connection cannot be trusted, answer
+            // and close it.
+            Err(err) => match err.status() {
+                Some(status) => {
+                    warn!("bad request: {}", err);
+                    stats::STATS.requests.inc("", status.code());
+                    let (status, content_type) =
+                        error(&mut self.body_buf, status, &err.to_string())?;
+                    (status, content_type, false)
+                }
+                None => return Err(err),
+            },
         };
 
-        stream.write(&self.response_buf)?;
+        self.response_buf.clear();
+        http::write_head(
+            &mut self.response_buf,
+            status,
+            content_type,
+            self.body_buf.len(),
+            keep_alive,
+        )?;
+        self.response_buf.extend_from_slice(&self.body_buf);
+        stream.write_all(&self.response_buf)?;
         stream.flush()?;
-        Ok(())
+        Ok((status, keep_alive, allocations.usage()))
     }
 }
 
-fn server(s: &'static str) {
-    let mut handler = Handler {
-        request_buf: Vec::with_capacity(12288),
-        response_buf: Vec::with_capacity(12288),
-        runner: snake::Runner::new(),
+/// Passes a request on to the runner, writing the response to `body` and
+/// returning its status and content type. Moves are always answered, with a
+/// fallback move when the search panics or overruns, and the request is
+/// logged so it can be replayed.
+fn route(
+    searcher: &mut Searcher,
+    config: &Config,
+    request: &http::Request,
+    body: &mut Vec<u8>,
+) -> serde_json::Result<(http::Status, &'static str)> {
+    let allowed = match request.path {
+        "/" | "/metrics" => "GET",
+        "/start" | "/end" | "/move" => "POST",
+        _ => return error(body, http::Status::NotFound, "not found"),
     };
-    let listener = TcpListener::bind(s).unwrap();
-    info!("listener started on {}", s);
+    if request.method != allowed {
+        return error(
+            body,
+            http::Status::MethodNotAllowed(allowed),
+            "method not allowed",
+        );
+    }
+    if request.path == "/" {
+        serde_json::to_writer(body, &config.appearance.response())?;
+        return Ok((http::Status::Ok, JSON));
+    }
+    if request.path == "/metrics" {
+        stats::STATS.write_prometheus(body).map_err(serde_json::Error::io)?;
+        return Ok((http::Status::Ok, METRICS));
+    }
+
+    let req = match api::MoveRequest::parse_owned(request.body) {
+        Ok(req) => Arc::new(req),
+        Err(err) => return error(body, http::Status::BadRequest, &err.to_string()),
+    };
+    let content = String::from_utf8_lossy(request.body);
+    let (job, deadline) = match request.path {
+        "/start" => (Job::Start(req.clone()), DEADLINE),
+        "/end" => (Job::End(req.clone()), DEADLINE),
+        _ => {
+            info!("/move request: {}", content);
+            (
+                Job::Move(req.clone()),
+                searcher.runner.deadline(req.game.timeout),
+            )
+        }
+    };
+    let failure = match (searcher.run(job, deadline), request.path) {
+        (Outcome::Done(Some(direction)), _) => {
+            serde_json::to_writer(body, &api::MoveResponse { direction })?;
+            return Ok((http::Status::Ok, JSON));
+        }
+        (Outcome::Done(None), _) => return Ok((http::Status::Ok, JSON)),
+        (Outcome::Panicked, "/move") => "panicked",
+        (Outcome::Overrun, "/move") => "overran its deadline",
+        (Outcome::Panicked, path) => {
+            error!("{} panicked, request: {}", path, content);
+            return error(body, http::Status::InternalServerError, "request failed");
+        }
+        (Outcome::Overrun, path) => {
+            warn!("{} overran its deadline, request: {}", path, content);
+            return Ok((http::Status::Ok, JSON));
+        }
+    };
+    let direction = snake::fallback(&req);
+    error!(
+        "search {}, falling back to {:?}, request: {}",
+        failure, direction, content
+    );
+    serde_json::to_writer(body, &api::MoveResponse { direction })?;
+    Ok((http::Status::Ok, JSON))
+}
+
+fn error(
+    body: &mut Vec<u8>,
+    status: http::Status,
+    message: &str,
+) -> serde_json::Result<(http::Status, &'static str)> {
+    body.clear();
+    serde_json::to_writer(body, &api::ErrorResponse { error: message })?;
+    Ok((status, JSON))
+}
+
+/// Serves the snake on the configured address until the process exits.
+fn server(config: Config) {
+    let listen = &config.server.listen;
+    let listener = TcpListener::bind(listen).unwrap_or_else(|err| {
+        error!("http: cannot listen on {}: {}", listen, err);
+        process::exit(1);
+    });
+    info!(
+        "listener started on {} with {} workers",
+        listen, config.server.workers
+    );
+    accept(listener, config);
+}
+
+/// Accepts connections on the calling thread and hands them to a pool of
+/// workers, each with a handler and runner of its own.
+fn accept(listener: TcpListener, config: Config) {
+    let runner = config.runner().unwrap();
+    let config = Arc::new(config);
+    let waiting = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel::<TcpStream>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for i in 0..config.server.workers {
+        let receiver = Arc::clone(&receiver);
+        let name = format!("worker-{}", i);
+        let waiting = Arc::clone(&waiting);
+        let mut handler = Handler::new(
+            runner.worker(),
+            Arc::clone(&config),
+            Arc::clone(&waiting),
+            &name,
+        );
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || loop {
+                let stream = match receiver.lock().unwrap().recv() {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                waiting.fetch_sub(1, Ordering::Relaxed);
+                // Keep the worker when anything else panics, the connection
+                // is dropped.
+                if panic::catch_unwind(AssertUnwindSafe(|| handler.serve(stream))).is_err() {
+                    error!("http: connection handler panicked");
+                }
+            })
+            .unwrap();
+    }
+
     for stream in listener.incoming() {
         match stream {
-            Ok(mut stream) => {
-                let now = Instant::now();
-                match handler.handle(&mut stream) {
-                    Ok(_) => info!(
-                        "request ok: in {:?}, stats: {}",
-                        now.elapsed(),
-                        stats::STATS
-                    ),
-                    Err(err) => error!("request failed: {}", err),
-                }
+            Ok(stream) => {
+                waiting.fetch_add(1, Ordering::Relaxed);
+                sender.send(stream).unwrap();
             }
             Err(e) => error!("http: connection failed {}", e),
         }
     }
 }
 
+/// The error of a subcommand failing to write its output.
+fn writing(err: io::Error) -> String {
+    format!("writing output: {}", err)
+}
+
+/// Runs a subcommand and exits, with 1 when it finds a difference and 2 when
+/// it fails.
+fn subcommand<F>(name: &str, usage: &str, help: bool, run: F) -> !
+where
+    F: FnOnce(&mut io::StdoutLock) -> Result<bool, String>,
+{
+    if help {
+        print!("{}", usage);
+        process::exit(0);
+    }
+    match run(&mut io::stdout().lock()) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("snake {}: {}", name, err);
+            process::exit(2);
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
-    server("0.0.0.0:3000");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let help = args.iter().any(|arg| arg == "--help" || arg == "-h");
+    let env = |var: &str| env::var(var).ok();
+    match args.first().map(String::as_str) {
+        Some("replay") => subcommand("replay", replay::USAGE, help, |out| {
+            replay::run(&args[1..], env, out)
+        }),
+        Some("simulate") => subcommand("simulate", simulate::USAGE, help, |out| {
+            simulate::run(&args[1..], env, out).map(|()| true)
+        }),
+        Some("tournament") => subcommand("tournament", tournament::USAGE, help, |out| {
+            tournament::run(&args[1..], env, out).map(|()| true)
+        }),
+        _ => {}
+    }
+    if help {
+        print!("{}", config::usage());
+        return;
+    }
+    let config = Config::load(&args, env).unwrap_or_else(|err| {
+        eprintln!("snake: {}", err);
+        process::exit(2);
+    });
+    server(config);
 }
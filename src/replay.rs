@@ -0,0 +1,168 @@
+use super::api;
+use super::config::{self, flag_value, Config};
+use super::writing;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+
+#[cfg(test)]
+mod tests;
+
+/// What precedes a request body in the server log.
+const LOGGED: &str = "/move request: ";
+
+pub const USAGE: &str = "\
+usage: snake replay FILE [--diff] [--tree-depth N] [--FLAG VALUE]...
+
+Runs the /move requests in FILE through the search again and prints the move
+picked, the board and the top of the search tree. FILE holds a request per
+line, as JSON or as logged by the server, other lines are skipped. The move
+played originally is read from the next turn of the same game, if logged.
+
+  --diff          only print the turns picked differently, exit with 1 if any
+  --tree-depth N  moves of the search tree to print, 1 by default, 0 for none
+
+Other flags configure the search as they do the server, see snake --help,
+except that it runs on one thread unless --threads says otherwise. The search
+still stops when the time of the turn is up, give --max-depth as well to pick
+the same moves on every run.
+";
+
+struct Options {
+    path: String,
+    diff: bool,
+    tree_depth: usize,
+    config: Vec<String>,
+}
+
+/// Replays the requests given by `args` and writes what was picked to `out`.
+/// Returns false when diffing and some move played was picked differently.
+pub fn run<F, W>(args: &[String], env: F, out: &mut W) -> Result<bool, String>
+where
+    F: Fn(&str) -> Option<String>,
+    W: Write,
+{
+    let opts = parse_args(args)?;
+    let mut config = Config::load(&opts.config, env)?;
+    // Threads racing for moves would search a little differently every run.
+    if config.search.threads == 0 {
+        config.search.threads = 1;
+    }
+    let mut runner = config.runner()?;
+    if opts.tree_depth > 0 {
+        runner.set_tree_depth(Some(opts.tree_depth));
+    }
+
+    let content =
+        fs::read_to_string(&opts.path).map_err(|err| format!("{}: {}", opts.path, err))?;
+    let mut requests = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let body = match line.find(LOGGED) {
+            Some(at) => &line[at + LOGGED.len()..],
+            None => line.trim(),
+        };
+        if !body.starts_with('{') {
+            continue;
+        }
+        let req: api::MoveRequest = serde_json::from_str(body)
+            .map_err(|err| format!("{}:{}: {}", opts.path, i + 1, err))?;
+        requests.push(req);
+    }
+
+    // Where each turn of each of our snakes is, to find the move played next.
+    let turns: HashMap<_, _> = requests
+        .iter()
+        .enumerate()
+        .map(|(i, req)| ((&*req.game.id, &*req.you.id, req.turn), i))
+        .collect();
+
+    let mut started = HashSet::new();
+    let (mut known, mut differ) = (0, 0);
+    for req in &requests {
+        if started.insert(&*req.game.id) {
+            runner.start(req);
+        }
+        let (picked, diagnostics) = runner.run(req);
+        let played = turns
+            .get(&(&*req.game.id, &*req.you.id, req.turn + 1))
+            .and_then(|next| played(req, &requests[*next]));
+        let same = played.as_ref().is_none_or(|played| *played == picked);
+        known += played.is_some() as usize;
+        differ += !same as usize;
+        if opts.diff && same {
+            continue;
+        }
+
+        match &played {
+            Some(played) => writeln!(
+                out,
+                "game {} turn {}: picked {}, played {}",
+                req.game.id, req.turn, picked, played
+            ),
+            None => writeln!(
+                out,
+                "game {} turn {}: picked {}",
+                req.game.id, req.turn, picked
+            ),
+        }
+        .map_err(writing)?;
+        writeln!(out, "{}", diagnostics).map_err(writing)?;
+        write!(out, "{}", runner.board(req)).map_err(writing)?;
+        if let Some(tree) = diagnostics.tree() {
+            write!(out, "{}", tree).map_err(writing)?;
+        }
+        writeln!(out).map_err(writing)?;
+    }
+    writeln!(
+        out,
+        "{} moves replayed, {} of {} played picked differently",
+        requests.len(),
+        differ,
+        known
+    )
+    .map_err(writing)?;
+    Ok(!opts.diff || differ == 0)
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        path: String::new(),
+        diff: false,
+        tree_depth: 1,
+        config: Vec::new(),
+    };
+    let rest = config::split_flags(args, &["diff"], |name, value| {
+        match name {
+            "diff" if value.is_none() => opts.diff = true,
+            "diff" => return Err(format!("--diff: unexpected value {:?}", value.unwrap())),
+            "tree-depth" => opts.tree_depth = flag_value(name, value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+    let mut positional = rest.positional.into_iter();
+    opts.path = positional
+        .next()
+        .ok_or("missing the file to replay, see --help")?;
+    if let Some(arg) = positional.next() {
+        return Err(format!("unexpected argument {:?}, see --help", arg));
+    }
+    opts.config = rest.config;
+    Ok(opts)
+}
+
+/// The move we played in `req`, when `next` is the following turn of the
+/// same game, from where our head went.
+fn played(req: &api::MoveRequest, next: &api::MoveRequest) -> Option<api::Direction> {
+    if next.game.id != req.game.id || next.you.id != req.you.id || next.turn != req.turn + 1 {
+        return None;
+    }
+    let (from, to) = (req.you.body.first()?, next.you.body.first()?);
+    match (to.x - from.x, to.y - from.y) {
+        (0, 1) => Some(api::Direction::Up),
+        (0, -1) => Some(api::Direction::Down),
+        (-1, 0) => Some(api::Direction::Left),
+        (1, 0) => Some(api::Direction::Right),
+        _ => None,
+    }
+}
@@ -0,0 +1,101 @@
+use super::super::testing::{self, args, TempFile};
+use super::*;
+
+/// A request of a solo game where we have food right above our head.
+fn request(turn: u16, head: (i16, i16)) -> String {
+    let body = format!(
+        r#"[{{"x":{},"y":{}}},{{"x":{},"y":{}}},{{"x":{},"y":{}}}]"#,
+        head.0,
+        head.1,
+        head.0,
+        head.1 - 1,
+        head.0,
+        head.1 - 2
+    );
+    let you = format!(
+        r#"{{"id":"me","health":90,"body":{},"head":{{"x":{},"y":{}}},"length":3}}"#,
+        body, head.0, head.1
+    );
+    format!(
+        r#"{{"game":{{"id":"replay","ruleset":{{"name":"solo","version":"v1"}},"timeout":500}},"turn":{},"board":{{"height":11,"width":11,"food":[{{"x":{},"y":{}}}],"snakes":[{}]}},"you":{}}}"#,
+        turn,
+        head.0,
+        head.1 + 1,
+        you,
+        you
+    )
+}
+
+fn replay(name: &str, content: &str, flags: &[&str]) -> (Result<bool, String>, String) {
+    let file = TempFile::with(&format!("replay-{}", name), content);
+    let mut all = args(&[file.path()]);
+    all.extend(args(flags));
+    let mut out = Vec::new();
+    let result = run(&all, |_| None, &mut out);
+    (result, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn diffs_against_moves_played() {
+    // The server log, we went left for the food instead of eating it.
+    let log = format!(
+        "[2026-10-18T07:48:28Z INFO  snake] /move request: {}\n\
+         [2026-10-18T07:48:28Z INFO  snake] request 200 OK: in 1ms\n\
+         {}\n",
+        request(1, (5, 5)),
+        request(2, (4, 5))
+    );
+    let (result, out) = replay("diff", &log, &["--diff", "--strategy", "greedy"]);
+    assert_eq!(result, Ok(false), "{}", out);
+    assert!(
+        out.starts_with("game replay turn 1: picked up, played left\n"),
+        "{}",
+        out
+    );
+    assert!(!out.contains("turn 2"), "{}", out);
+    assert!(
+        out.ends_with("2 moves replayed, 1 of 1 played picked differently\n"),
+        "{}",
+        out
+    );
+
+    // Without diffing every turn is printed and nothing fails.
+    let (result, out) = replay("all", &log, &["--strategy=greedy", "--tree-depth", "0"]);
+    assert_eq!(result, Ok(true));
+    assert!(out.contains("game replay turn 2: picked up\n"), "{}", out);
+}
+
+#[test]
+fn prints_the_search_tree() {
+    let (result, out) = replay(
+        "tree",
+        &request(1, (5, 5)),
+        &["--max-depth", "2", "--threads", "1", "--tree-depth", "1"],
+    );
+    assert_eq!(result, Ok(true));
+    assert!(out.contains("|- Root [0] up\n"), "{}", out);
+    // Only the moves at the root.
+    assert_eq!(out.matches("|- ").count(), 5, "{}", out);
+}
+
+#[test]
+fn rejects_invalid_input() {
+    let file = TempFile::with("replay-invalid", "");
+    let cases: &[(&[&str], &str)] = &[
+        (&["--tree-depth", "deep"], "--tree-depth: invalid digit"),
+        (&["--diff=no"], "--diff: unexpected value"),
+        (&["--max-depht", "3"], "unknown flag --max-depht"),
+        (&["other"], "unexpected argument \"other\""),
+    ];
+    testing::assert_rejects(cases, |flags| {
+        let mut all = args(&[file.path()]);
+        all.extend_from_slice(flags);
+        run(&all, |_| None, &mut Vec::new())
+    });
+    testing::assert_rejects(&[(&[], "missing the file")], |flags| {
+        run(flags, |_| None, &mut Vec::new())
+    });
+
+    let (result, _) = replay("malformed", "{\"turn\": 1}\n", &[]);
+    assert!(result.unwrap_err().contains(":1: missing field"));
+}
@@ -0,0 +1,134 @@
+use super::config::{self, flag_value, Config};
+use super::snake::sim::{self, Player, Remote, Rules};
+use super::snake::Runner;
+use super::writing;
+use std::fs;
+use std::io::Write;
+
+#[cfg(test)]
+mod tests;
+
+pub const USAGE: &str = "\
+usage: snake simulate PLAYER... [--seed N] [--log FILE] [--RULE VALUE]... [--FLAG VALUE]...
+
+Plays a game between the players under the standard ruleset and prints how it
+went. A player is a strategy played in process, like minimax or greedy, or the
+URL of a snake served on this machine, like http://127.0.0.1:3000.
+
+  --seed N               draws the start and the food, 1 by default
+  --log FILE             writes every /move request sent to FILE, one a line
+  --width N              11 by default
+  --height N             11 by default
+  --food-spawn-chance N  percent, 15 by default
+  --minimum-food N       1 by default
+  --timeout N            milliseconds a snake has to answer, 500 by default
+  --max-turns N          turns after which the game is a draw, 1000 by default
+
+Other flags configure the players played in process as they do the server,
+see snake --help.
+";
+
+struct Options {
+    players: Vec<String>,
+    rules: Rules,
+    seed: u64,
+    log: Option<String>,
+    config: Vec<String>,
+}
+
+/// Plays the game given by `args` and writes how it went to `out`.
+pub fn run<F, W>(args: &[String], env: F, out: &mut W) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<String>,
+    W: Write,
+{
+    let opts = parse_args(args)?;
+    let config = Config::load(&opts.config, env)?;
+    let mut players = Vec::new();
+    for player in &opts.players {
+        players.push(player_of(player, &config)?);
+    }
+
+    let id = format!("sim-{}", opts.seed);
+    let outcome = sim::play(&id, &opts.rules, opts.seed, &mut players);
+    if let Some(path) = &opts.log {
+        let mut log = String::new();
+        for line in &outcome.log {
+            log.push_str(line);
+            log.push('\n');
+        }
+        fs::write(path, log).map_err(|err| format!("{}: {}", path, err))?;
+    }
+
+    write!(out, "{}", outcome.board).map_err(writing)?;
+    let result = match outcome.winner {
+        Some(i) => format!("snake-{} ({}) won", i, opts.players[i]),
+        None if opts.players.len() == 1 => "solo".to_string(),
+        None => "draw".to_string(),
+    };
+    writeln!(
+        out,
+        "game {} over after {} turns, {}",
+        id, outcome.turns, result
+    )
+    .map_err(writing)?;
+    for (i, (player, eliminated)) in opts.players.iter().zip(&outcome.eliminated).enumerate() {
+        match eliminated {
+            Some(turn) => writeln!(out, "  snake-{} {}: eliminated on turn {}", i, player, turn),
+            None => writeln!(out, "  snake-{} {}: still in", i, player),
+        }
+        .map_err(writing)?;
+    }
+    Ok(())
+}
+
+/// A snake served at a URL, or a runner playing the named strategy with the
+/// configured settings.
+pub fn player_of(name: &str, config: &Config) -> Result<Box<dyn Player>, String> {
+    if name.contains("://") {
+        return Ok(Box::new(Remote::new(name)?));
+    }
+    let mut runner: Runner = config.runner()?;
+    runner.set_strategy(None, name)?;
+    Ok(Box::new(runner))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        players: Vec::new(),
+        rules: Rules::default(),
+        seed: 1,
+        log: None,
+        config: Vec::new(),
+    };
+    let rest = config::split_flags(args, &[], |name, value| {
+        match name {
+            "seed" => opts.seed = flag_value(name, value)?,
+            "log" => opts.log = Some(flag_value(name, value)?),
+            _ => return set_rule(&mut opts.rules, name, value),
+        }
+        Ok(true)
+    })?;
+    opts.players = rest.positional;
+    opts.config = rest.config;
+    if opts.players.is_empty() {
+        return Err("no players, see --help".into());
+    }
+    opts.rules.validate()?;
+    Ok(opts)
+}
+
+/// Sets the rule flag `name` to `value`, false when there is no such rule.
+pub fn set_rule(rules: &mut Rules, name: &str, value: Option<&str>) -> Result<bool, String> {
+    let rule = match name {
+        "width" => &mut rules.width,
+        "height" => &mut rules.height,
+        "food-spawn-chance" => &mut rules.food_spawn_chance,
+        "minimum-food" => &mut rules.minimum_food,
+        "timeout" => &mut rules.timeout,
+        "max-turns" => &mut rules.max_turns,
+        _ => return Ok(false),
+    };
+    *rule = flag_value(name, value)?;
+    Ok(true)
+}
@@ -0,0 +1,47 @@
+use super::super::api;
+use super::super::testing::{self, args, TempFile};
+use super::*;
+
+#[test]
+fn plays_a_game() {
+    let log = TempFile::new("simulate");
+    let all = args(&[
+        "greedy",
+        "greedy",
+        "--seed",
+        "3",
+        "--max-turns=20",
+        "--threads",
+        "1",
+        "--log",
+        log.path(),
+    ]);
+    let mut out = Vec::new();
+    run(&all, |_| None, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("game sim-3 over after "), "{}", out);
+    assert!(out.contains("  snake-0 greedy: "), "{}", out);
+    assert!(out.contains("  snake-1 greedy: "), "{}", out);
+
+    let content = fs::read_to_string(log.path()).unwrap();
+    assert!(!content.is_empty());
+    for line in content.lines() {
+        let req: api::MoveRequest = serde_json::from_str(line).unwrap();
+        assert_eq!(req.game.id, "sim-3");
+        assert!(req.turn < 20);
+    }
+}
+
+#[test]
+fn rejects_invalid_input() {
+    let cases: &[(&[&str], &str)] = &[
+        (&[], "no players"),
+        (&["greedy", "--seed", "many"], "--seed: invalid digit"),
+        (&["greedy", "--width", "30"], "width and height"),
+        (&["greedy", "--log"], "--log: missing value"),
+        (&["greedy", "--max-depht", "3"], "unknown flag --max-depht"),
+        (&["greediest"], "greediest"),
+        (&["https://example.com"], "only http://"),
+    ];
+    testing::assert_rejects(cases, |flags| run(flags, |_| None, &mut Vec::new()));
+}
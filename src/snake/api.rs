@@ -1,54 +1,238 @@
 use super::super::api;
-use super::minmax::Walker;
+use super::super::stats::STATS;
+use super::clock::{self, Position};
+use super::session::{Session, Sessions};
+use super::strategy::{self, Diagnostics, Meta};
 use super::*;
 use log::*;
+use std::collections::HashMap;
 use std::convert::TryInto;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_STRATEGY: &str = "minimax";
 
 pub struct Runner {
-    walker: Walker,
+    /// Strategy name per ruleset name, falling back to `default`.
+    rulesets: HashMap<String, &'static str>,
+    default: &'static str,
+    /// Time kept back from the game timeout for the network.
+    margin: Duration,
+    /// Share of the game timeout before the move is given up on.
+    deadline_percent: u32,
+    threads: usize,
+    tree_depth: Option<usize>,
+    settings: Settings,
+    sessions: Sessions,
 }
 
 impl Runner {
     pub fn new() -> Runner {
+        let mut runner = Runner {
+            rulesets: HashMap::new(),
+            default: DEFAULT_STRATEGY,
+            margin: clock::DEFAULT_MARGIN,
+            deadline_percent: clock::DEFAULT_DEADLINE_PERCENT,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tree_depth: None,
+            settings: Settings::default(),
+            sessions: Sessions::default(),
+        };
+        runner.set_strategy(None, DEFAULT_STRATEGY).unwrap();
+        runner
+    }
+
+    /// Uses the named strategy for games of the given ruleset, or for every
+    /// ruleset without one of its own when `ruleset` is `None`.
+    pub fn set_strategy(&mut self, ruleset: Option<&str>, name: &str) -> std::result::Result<(), String> {
+        let name = *strategy::STRATEGIES
+            .iter()
+            .find(|known| **known == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown strategy {:?}, expected one of {:?}",
+                    name,
+                    strategy::STRATEGIES
+                )
+            })?;
+        match ruleset {
+            Some(ruleset) => {
+                self.rulesets.insert(ruleset.to_string(), name);
+            }
+            None => self.default = name,
+        }
+        Ok(())
+    }
+
+    /// Keeps `margin` of every timeout back for the network, on top of the
+    /// latency measured over the game.
+    pub fn set_margin(&mut self, margin: Duration) {
+        self.margin = margin;
+    }
+
+    /// Gives up on moves after `percent` of the game timeout, searches are
+    /// budgeted to end before then.
+    pub fn set_deadline_percent(&mut self, percent: u32) {
+        self.deadline_percent = percent;
+    }
+
+    /// When a move for a game with this timeout is given up on.
+    pub fn deadline(&self, timeout_ms: u16) -> Duration {
+        Duration::from_millis(timeout_ms as u64) * self.deadline_percent / 100
+    }
+
+    /// Another runner with the same configuration and sessions, for a worker
+    /// thread. Strategies keep search state of their own in the session of
+    /// each game, whichever runner picks its moves.
+    pub fn worker(&self) -> Runner {
         Runner {
-            walker: Walker::new(),
+            rulesets: self.rulesets.clone(),
+            default: self.default,
+            margin: self.margin,
+            deadline_percent: self.deadline_percent,
+            threads: self.threads,
+            tree_depth: self.tree_depth,
+            settings: self.settings,
+            sessions: self.sessions.clone(),
         }
     }
 
-    pub fn run(&mut self, req: &api::MoveRequest) -> api::Direction {
-        let board = self.from_request(req);
-        let node = self.walker.walk(
-            board,
-            Options {
-                max_depth: 20,
-                sla: Duration::from_millis(75),
-            },
+    /// Uses other search limits and weights for the games started after.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+    }
+
+    /// Lets strategies search on this many threads, all cores by default.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Has strategies that build a search tree return it with their
+    /// diagnostics, down to `depth` moves from the root.
+    pub fn set_tree_depth(&mut self, depth: Option<usize>) {
+        self.tree_depth = depth;
+    }
+
+    pub fn start(&mut self, req: &api::MoveRequest) {
+        info!("game {} started", req.game.id);
+        self.sessions.insert(&req.game.id, Session::new(self.margin));
+    }
+
+    pub fn end(&mut self, req: &api::MoveRequest) {
+        match self.sessions.take(&req.game.id) {
+            Some(session) => info!(
+                "game {} ended after {} turns",
+                req.game.id, session.turn
+            ),
+            None => warn!("game {} ended without a session", req.game.id),
+        }
+    }
+
+    /// Picks a move, returned with how the strategy came to it.
+    pub fn run(&mut self, req: &api::MoveRequest) -> (api::Direction, Diagnostics) {
+        let start = Instant::now();
+        let margin = self.margin;
+        let mut session = self.sessions.lend(&req.game.id, || {
+            warn!("game {} has no session, starting one", req.game.id);
+            Session::new(margin)
+        });
+        session.observe(req);
+        for snake in &req.board.snakes {
+            if snake.id != req.you.id {
+                debug!("snake {} moves: {:?}", snake.id, session.moves(&snake.id));
+            }
+        }
+        if let Some(previous) = &session.previous {
+            debug!("previous board:\n{}", previous);
+        }
+
+        let board = match self.read_board(req) {
+            Ok(board) => board,
+            Err(err) => {
+                warn!("game {} turn {}: {}, falling back", req.game.id, req.turn, err);
+                return (fallback(req), Diagnostics::default());
+            }
+        };
+        if log_enabled!(Level::Debug) {
+            debug!("board:\n{}", board);
+            debug!("territory:\n{}", space::voronoi(&board));
+        }
+
+        let position = Position::of(&board);
+        let deadline = self.deadline(req.game.timeout);
+        let sla = session.clock.budget(req.game.timeout, deadline, position);
+        debug!(
+            "{:?} position, budget {:?} of {}ms",
+            position, sla, req.game.timeout
         );
 
-        debug!("board:\n{}", node.board);
-        debug!("tree:\n{}", node);
+        let ruleset = &*req.game.ruleset.name;
+        let name = self.rulesets.get(ruleset).copied().unwrap_or(self.default);
+        let settings = &self.settings;
+        let strategy = session
+            .strategies
+            .entry(name)
+            .or_insert_with(|| strategy::by_name(name, settings).unwrap());
+        let decision = strategy.decide(
+            &board,
+            &Meta {
+                game: &req.game.id,
+                ruleset,
+                turn: req.turn,
+                sla,
+                threads: self.threads,
+                tree_depth: self.tree_depth,
+            },
+        );
+        STATS.search_depth.observe(decision.diagnostics.depth as f64);
+        STATS.search_nodes.inc_by(decision.diagnostics.nodes);
+        STATS
+            .search_seconds
+            .observe(decision.diagnostics.elapsed.as_secs_f64());
+        info!(
+            "{} picked {}, {}",
+            strategy.name(),
+            decision.moved,
+            decision.diagnostics
+        );
+        session.previous = Some(board);
+        session.clock.spent(start.elapsed());
+        drop(session);
 
-        match node.pick() {
+        let direction = match decision.moved {
             Move::Up => api::Direction::Up,
             Move::Down => api::Direction::Down,
             Move::Left => api::Direction::Left,
             Move::Right => api::Direction::Right,
+        };
+        (direction, decision.diagnostics)
+    }
+
+    /// The board of a request as the strategies see it, us as snake 0, or
+    /// why there is none.
+    pub fn board(&self, req: &api::MoveRequest) -> String {
+        match self.read_board(req) {
+            Ok(board) => board.to_string(),
+            Err(err) => format!("{}\n", err),
         }
     }
 
-    fn from_request(&mut self, req: &api::MoveRequest) -> Board {
+    pub(super) fn read_board(&self, req: &api::MoveRequest) -> std::result::Result<Board, String> {
+        let (width, height) = (req.board.width, req.board.height);
+        if !Game::fits(width, height) {
+            return Err(format!("unsupported board size {}x{}", width, height));
+        }
         let mut snakes = Vec::new();
         let mut food = Vec::new();
         for point in &req.board.food {
-            food.push((point.x.into(), point.y.into()));
+            food.push(pos(point));
         }
 
         let mut my_body = Vec::with_capacity(req.you.body.len());
         for point in &req.you.body {
-            my_body.push((point.x.into(), point.y.into()));
+            my_body.push(pos(point));
         }
-        snakes.push(Snake::new(my_body));
+        snakes.push(Snake::new(my_body).with_health(req.you.health as isize));
 
         for snake in &req.board.snakes {
             if snake.id == req.you.id {
@@ -56,14 +240,84 @@ impl Runner {
             }
             let mut snake_body = Vec::with_capacity(snake.body.len());
             for point in &snake.body {
-                snake_body.push((point.x.into(), point.y.into()));
+                snake_body.push(pos(point));
             }
-            snakes.push(Snake::new(snake_body));
+            snakes.push(Snake::new(snake_body).with_health(snake.health as isize));
+        }
+        let mut hazards = Vec::with_capacity(req.board.hazards.len());
+        for point in &req.board.hazards {
+            hazards.push(pos(point));
+        }
+
+        let mut game = Game::new(width as i8, height as i8);
+        // Older engines omit the ruleset settings, keep the standard damage.
+        if let Some(hazard_damage) = req.game.ruleset.settings.hazard_damage_per_turn {
+            game.hazard_damage = hazard_damage as isize;
+        }
+        game.max_health = self.settings.max_health as isize;
+        Ok(Board::new(game, snakes, food, hazards))
+    }
+}
+
+/// Converts an API point, anything that does not fit ends up off the board.
+pub(super) fn pos(point: &api::Point) -> Pos {
+    (
+        point.x.try_into().unwrap_or(i8::MIN),
+        point.y.try_into().unwrap_or(i8::MIN),
+    )
+}
+
+/// A move that does not lose right away if there is one, worked out from the
+/// request alone so it works even when the board cannot be built. Used when
+/// the search fails. Moves next to the head of a snake at least as long or
+/// onto a hazard are only taken when nothing else is left.
+pub fn fallback(req: &api::MoveRequest) -> api::Direction {
+    let head = match req.you.body.first() {
+        Some(head) => head,
+        None => return api::Direction::Up,
+    };
+    let (width, height) = (req.board.width as i32, req.board.height as i32);
+    let occupied = |x: i32, y: i32| {
+        req.board.snakes.iter().any(|snake| {
+            let len = snake.body.len();
+            // A tail that is not stacked moves out of the way.
+            let tail = len > 1 && snake.body[len - 1] != snake.body[len - 2];
+            snake.body[..if tail { len - 1 } else { len }]
+                .iter()
+                .any(|p| p.x as i32 == x && p.y as i32 == y)
+        })
+    };
+    let contested = |x: i32, y: i32| {
+        req.board.snakes.iter().any(|snake| {
+            snake.id != req.you.id
+                && snake.body.len() >= req.you.body.len()
+                && snake.body.first().is_some_and(|other| {
+                    (other.x as i32 - x).abs() + (other.y as i32 - y).abs() == 1
+                })
+        })
+    };
+
+    let mut best = (api::Direction::Up, i32::MIN);
+    for (direction, dx, dy) in [
+        (api::Direction::Up, 0, 1),
+        (api::Direction::Down, 0, -1),
+        (api::Direction::Left, -1, 0),
+        (api::Direction::Right, 1, 0),
+    ] {
+        let (x, y) = (head.x as i32 + dx, head.y as i32 + dy);
+        let mut score = 0;
+        if x < 0 || y < 0 || x >= width || y >= height || occupied(x, y) {
+            score -= 100;
+        }
+        if contested(x, y) {
+            score -= 10;
+        }
+        if req.board.hazards.iter().any(|p| p.x as i32 == x && p.y as i32 == y) {
+            score -= 1;
+        }
+        if score > best.1 {
+            best = (direction, score);
         }
-        let game = Game {
-            width: req.board.width.try_into().unwrap(),
-            height: req.board.height.try_into().unwrap(),
-        };
-        Board::new(game, snakes, food)
     }
+    best.0
 }
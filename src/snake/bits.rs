@@ -0,0 +1,139 @@
+use super::Pos;
+use smallvec::SmallVec;
+
+const WORDS: usize = 10;
+
+/// Fixed size set of board squares, large enough for a 25x25 board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(super) struct BitSet([u64; WORDS]);
+
+impl BitSet {
+    pub(super) const CAPACITY: usize = WORDS * 64;
+
+    pub(super) fn new() -> BitSet {
+        BitSet([0; WORDS])
+    }
+
+    pub(super) fn has(&self, i: usize) -> bool {
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub(super) fn set(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    pub(super) fn unset(&mut self, i: usize) {
+        self.0[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.0 = [0; WORDS];
+    }
+
+    /// Indexes of the squares in the set, in order.
+    pub(super) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(w, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Snake body as a ring buffer, so moving only overwrites the tail slot with
+/// the new head. Short bodies are stored inline and copy without allocating.
+#[derive(Debug, Default)]
+pub(super) struct Body {
+    buf: SmallVec<[Pos; 24]>,
+    head: usize,
+}
+
+/// Copying into an existing body reuses its buffer, long bodies included,
+/// and leaves room for the body to grow once.
+impl Clone for Body {
+    fn clone(&self) -> Body {
+        Body {
+            buf: self.buf.clone(),
+            head: self.head,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Body) {
+        self.buf.clear();
+        self.buf.reserve(source.buf.len() + 1);
+        self.buf.extend_from_slice(&source.buf);
+        self.head = source.head;
+    }
+}
+
+impl Body {
+    pub(super) fn new(body: Vec<Pos>) -> Body {
+        Body {
+            buf: SmallVec::from_vec(body),
+            head: 0,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Segment `i` counting from the head.
+    pub(super) fn get(&self, i: usize) -> Pos {
+        self.buf[(self.head + i) % self.buf.len()]
+    }
+
+    pub(super) fn head(&self) -> Pos {
+        self.buf[self.head]
+    }
+
+    pub(super) fn first(&self) -> Option<Pos> {
+        self.buf.get(self.head).copied()
+    }
+
+    pub(super) fn tail(&self) -> Pos {
+        self.get(self.buf.len() - 1)
+    }
+
+    /// Moves the head to `pos` and drops the tail, returning the old tail.
+    pub(super) fn advance(&mut self, pos: Pos) -> Pos {
+        let tail = self.tail_index();
+        let old = self.buf[tail];
+        self.buf[tail] = pos;
+        self.head = tail;
+        old
+    }
+
+    /// Stacks a copy of the tail, the way snakes grow after eating.
+    pub(super) fn grow(&mut self) {
+        let tail = self.tail_index();
+        let pos = self.buf[tail];
+        self.buf.insert(tail + 1, pos);
+        if self.head > tail {
+            self.head += 1;
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.buf.clear();
+        self.head = 0;
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = Pos> + '_ {
+        (0..self.buf.len()).map(move |i| self.get(i))
+    }
+
+    fn tail_index(&self) -> usize {
+        (self.head + self.buf.len() - 1) % self.buf.len()
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+use std::collections::VecDeque;
+
+/// Time kept back for the network when nothing is configured.
+pub(super) const DEFAULT_MARGIN: Duration = Duration::from_millis(50);
+/// Share of the timeout a move may take before the server gives up on it.
+pub(super) const DEFAULT_DEADLINE_PERCENT: u32 = 80;
+
+/// Turns of latency history kept per game.
+const HISTORY: usize = 16;
+/// The budget never drops below this, a rushed move beats a random one.
+const MIN_BUDGET: Duration = Duration::from_millis(5);
+
+/// How much a position deserves thinking about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Position {
+    /// At most one move does not lose right away.
+    Trivial,
+    Normal,
+    /// Another head is close or we are running out of room.
+    Critical,
+}
+
+impl Position {
+    pub(super) fn of(board: &Board) -> Position {
+        let me = board.snake(SnakeID(0));
+        let game = &board.game;
+        let open = Move::all()
+            .iter()
+            .filter_map(|m| game.index(m.next(me.head())))
+            .filter(|sq| !space::is_blocked(board, *sq, 1))
+            .count();
+        if open <= 1 {
+            return Position::Trivial;
+        }
+        let (x, y) = me.head();
+        let close = board.snakes.iter().skip(1).any(|other| {
+            if !other.is_alive() {
+                return false;
+            }
+            let (ox, oy) = other.head();
+            (x - ox).abs() + (y - oy).abs() <= 3
+        });
+        if close || space::flood_fill(board, SnakeID(0)) < 2 * me.len() {
+            return Position::Critical;
+        }
+        Position::Normal
+    }
+
+    /// Percentage of the available time spent on the position.
+    fn share(self) -> u32 {
+        match self {
+            Position::Trivial => 20,
+            Position::Normal => 60,
+            Position::Critical => 100,
+        }
+    }
+}
+
+/// Search budget for a game. The engine reports the round trip of our
+/// previous response as our latency, what we did not spend deciding went to
+/// the network and the engine, and that overhead is kept back next turn.
+pub(super) struct Clock {
+    margin: Duration,
+    overheads: VecDeque<Duration>,
+    spent: Option<Duration>,
+}
+
+impl Clock {
+    pub(super) fn new(margin: Duration) -> Clock {
+        Clock {
+            margin,
+            overheads: VecDeque::with_capacity(HISTORY),
+            spent: None,
+        }
+    }
+
+    /// Records the latency reported for our previous response, if any.
+    pub(super) fn observe(&mut self, latency_ms: Option<u32>) {
+        let (latency, spent) = match (latency_ms, self.spent.take()) {
+            (Some(ms), Some(spent)) => (Duration::from_millis(ms as u64), spent),
+            _ => return,
+        };
+        if self.overheads.len() == HISTORY {
+            self.overheads.pop_front();
+        }
+        self.overheads.push_back(latency.saturating_sub(spent));
+    }
+
+    /// Records the time taken to answer this turn.
+    pub(super) fn spent(&mut self, elapsed: Duration) {
+        self.spent = Some(elapsed);
+    }
+
+    /// Worst overhead seen recently. Without history assume half the
+    /// timeout goes to the network.
+    pub(super) fn overhead(&self, timeout: Duration) -> Duration {
+        match self.overheads.iter().max() {
+            Some(overhead) => *overhead,
+            None => timeout / 2,
+        }
+    }
+
+    /// Time to search a position for. The margin is also kept back from
+    /// `deadline`, when the server gives up on the move, so the search winds
+    /// down before then however little the overhead.
+    pub(super) fn budget(
+        &self,
+        timeout_ms: u16,
+        deadline: Duration,
+        position: Position,
+    ) -> Duration {
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        let available = timeout
+            .saturating_sub(self.margin)
+            .saturating_sub(self.overhead(timeout))
+            .min(deadline.saturating_sub(self.margin));
+        (available * position.share() / 100).max(MIN_BUDGET)
+    }
+}
@@ -0,0 +1,84 @@
+use super::strategy::{Decision, Diagnostics, Meta, Strategy};
+use super::*;
+use std::time::Instant;
+
+/// Looks a single move ahead: avoids anything that can kill us next turn and
+/// heads for the most open space, or for food when hungry.
+pub(super) struct Greedy;
+
+impl Strategy for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn decide(&mut self, board: &Board, meta: &Meta) -> Decision {
+        let start = Instant::now();
+        // Constrictor snakes grow every turn and never need to eat.
+        let (moved, score) = pick(board, meta.ruleset != "constrictor");
+        Decision {
+            moved,
+            diagnostics: Diagnostics {
+                depth: 1,
+                nodes: 4,
+                score,
+                elapsed: start.elapsed(),
+                cut_short: false,
+                tree: None,
+            },
+        }
+    }
+}
+
+/// Best move by one move lookahead with its score, higher is better.
+pub(super) fn pick(board: &Board, eats: bool) -> (Move, f32) {
+    let me = board.snake(SnakeID(0));
+    if !me.is_alive() {
+        return (Move::Up, f32::NEG_INFINITY);
+    }
+    let hungry = eats && me.health < board.game.max_health / 2;
+
+    let mut best = (Move::Up, f32::NEG_INFINITY);
+    for m in Move::all() {
+        let next = m.next(me.head());
+        let score = match board.game.index(next) {
+            Some(sq) if !space::is_blocked(board, sq, 1) => {
+                let mut score = space::reachable(board, next, 1) as f32;
+                if threatened(board, next) {
+                    score -= 1000.0;
+                }
+                if hungry {
+                    score -= food_distance(board, next) as f32;
+                }
+                score
+            }
+            _ => f32::NEG_INFINITY,
+        };
+        if score > best.1 {
+            best = (m, score);
+        }
+    }
+    best
+}
+
+/// Whether a snake at least as long as us could move its head onto `pos`.
+fn threatened(board: &Board, pos: Pos) -> bool {
+    let me = board.snake(SnakeID(0));
+    board.snakes.iter().skip(1).any(|other| {
+        other.is_alive()
+            && other.len() >= me.len()
+            && Move::all().iter().any(|m| m.next(other.head()) == pos)
+    })
+}
+
+fn food_distance(board: &Board, pos: Pos) -> isize {
+    let mut closest = isize::from(board.game.width) + isize::from(board.game.height);
+    for y in 0..board.game.height {
+        for x in 0..board.game.width {
+            if let Square::Food = board.get((x, y)) {
+                let dist = (x - pos.0).abs() as isize + (y - pos.1).abs() as isize;
+                closest = closest.min(dist);
+            }
+        }
+    }
+    closest
+}
@@ -0,0 +1,296 @@
+use super::rng::Rng;
+use super::strategy::{Decision, Diagnostics, Meta, Strategy};
+use super::*;
+use std::time::Instant;
+
+/// How playouts pick moves for every snake.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Playout {
+    /// Uniformly among the moves that do not lose right away.
+    Random,
+    /// Like `Random`, but staying away from heads of snakes at least as long
+    /// and taking food when hungry.
+    Heuristic,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Stat {
+    visits: u32,
+    reward: f32,
+}
+
+struct Node {
+    board: Board,
+    visits: u32,
+    /// Moves each snake considers here, lethal ones left out.
+    moves: SmallVec<[Moves; 4]>,
+    /// Statistics per snake and move, snakes select their moves on their
+    /// own (decoupled UCT) since they all move at once.
+    stats: SmallVec<[[Stat; 4]; 4]>,
+    children: Vec<(Moves, usize)>,
+    terminal: bool,
+}
+
+type Rewards = SmallVec<[f32; 4]>;
+
+/// Monte Carlo tree search over whole turns.
+pub(super) struct Mcts {
+    playout: Playout,
+    opts: Options,
+    exploration: f32,
+    rng: Rng,
+    nodes: Vec<Node>,
+    path: Vec<(usize, Moves)>,
+}
+
+impl Mcts {
+    pub(super) fn new(playout: Playout, playout_depth: usize, seed: u64) -> Mcts {
+        Mcts {
+            playout,
+            opts: Options {
+                max_depth: playout_depth,
+                sla: Duration::from_secs(0),
+            },
+            exploration: std::f32::consts::SQRT_2,
+            rng: Rng::new(seed),
+            nodes: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+
+    fn node(&self, board: Board) -> Node {
+        let players = board.snakes.len();
+        let mut moves = SmallVec::with_capacity(players);
+        for i in 0..players {
+            moves.push(candidates(&board, SnakeID(i)));
+        }
+        let me = board.snake(SnakeID(0));
+        Node {
+            terminal: !me.is_alive() || (players > 1 && board.next_player(SnakeID(0)).is_none()),
+            board,
+            visits: 0,
+            moves,
+            stats: smallvec![[Stat::default(); 4]; players],
+            children: Vec::new(),
+        }
+    }
+
+    /// Runs one selection, expansion, playout and backup. Returns the depth
+    /// of the new leaf.
+    fn iterate(&mut self) -> usize {
+        self.path.clear();
+        let mut current = 0;
+        let rewards = loop {
+            if self.nodes[current].terminal {
+                break rewards(&self.nodes[current].board);
+            }
+            let joint = self.select(current);
+            let child = self.nodes[current]
+                .children
+                .iter()
+                .find(|(moves, _)| *moves == joint)
+                .map(|(_, child)| *child);
+            self.path.push((current, joint.clone()));
+            if let Some(child) = child {
+                current = child;
+                continue;
+            }
+
+            let mut board = self.nodes[current].board.clone();
+            board.apply_moves(&joint);
+            let node = self.node(board);
+            let rewards = self.simulate(node.board.clone());
+            self.nodes.push(node);
+            let child = self.nodes.len() - 1;
+            self.nodes[current].children.push((joint, child));
+            break rewards;
+        };
+
+        for (node, joint) in &self.path {
+            let node = &mut self.nodes[*node];
+            node.visits += 1;
+            for (i, m) in joint.iter().enumerate() {
+                if node.board.snakes[i].is_alive() {
+                    let stat = &mut node.stats[i][*m as usize];
+                    stat.visits += 1;
+                    stat.reward += rewards[i];
+                }
+            }
+        }
+        self.path.len()
+    }
+
+    /// UCB1 for every snake on its own statistics.
+    fn select(&mut self, node: usize) -> Moves {
+        let node = &self.nodes[node];
+        let ln_visits = (node.visits.max(1) as f32).ln();
+        let mut joint: Moves = smallvec![Move::Up; node.board.snakes.len()];
+        for (i, moves) in node.moves.iter().enumerate() {
+            let mut best = (Move::Up, -1.0);
+            for m in moves {
+                let stat = node.stats[i][*m as usize];
+                let value = if stat.visits == 0 {
+                    f32::INFINITY
+                } else {
+                    stat.reward / stat.visits as f32
+                        + self.exploration * (ln_visits / stat.visits as f32).sqrt()
+                };
+                if value > best.1 {
+                    best = (*m, value);
+                }
+            }
+            joint[i] = best.0;
+        }
+        joint
+    }
+
+    fn simulate(&mut self, mut board: Board) -> Rewards {
+        let players = board.snakes.len();
+        for _ in 0..self.opts.max_depth {
+            if !board.snake(SnakeID(0)).is_alive()
+                || (players > 1 && board.next_player(SnakeID(0)).is_none())
+            {
+                break;
+            }
+            let mut joint: Moves = smallvec![Move::Up; players];
+            for (i, m) in joint.iter_mut().enumerate() {
+                if board.snakes[i].is_alive() {
+                    *m = self.playout_move(&board, SnakeID(i));
+                }
+            }
+            board.apply_moves(&joint);
+        }
+        rewards(&board)
+    }
+
+    fn playout_move(&mut self, board: &Board, player: SnakeID) -> Move {
+        let mut moves = candidates(board, player);
+        if let Playout::Heuristic = self.playout {
+            let snake = board.snake(player);
+            let calm: Moves = moves
+                .iter()
+                .copied()
+                .filter(|m| !contested(board, player, m.next(snake.head())))
+                .collect();
+            if !calm.is_empty() {
+                moves = calm;
+            }
+            if snake.health < board.game.max_health / 3 {
+                let food = moves
+                    .iter()
+                    .find(|m| matches!(board.get(m.next(snake.head())), Square::Food));
+                if let Some(m) = food {
+                    return *m;
+                }
+            }
+        }
+        moves[self.rng.below(moves.len())]
+    }
+}
+
+impl Strategy for Mcts {
+    fn name(&self) -> &'static str {
+        match self.playout {
+            Playout::Random => "mcts-random",
+            Playout::Heuristic => "mcts",
+        }
+    }
+
+    fn decide(&mut self, board: &Board, meta: &Meta) -> Decision {
+        let start = Instant::now();
+        self.opts.sla = meta.sla;
+        self.nodes.clear();
+        let root = self.node(board.clone());
+        self.nodes.push(root);
+
+        let mut depth = 0;
+        loop {
+            depth = depth.max(self.iterate());
+            if start.elapsed() >= self.opts.sla {
+                break;
+            }
+        }
+
+        let root = &self.nodes[0];
+        let mut best = (Move::Up, 0, 0.0);
+        for m in &root.moves[0] {
+            let stat = root.stats[0][*m as usize];
+            if stat.visits > best.1 {
+                best = (*m, stat.visits, stat.reward / stat.visits as f32);
+            }
+        }
+        Decision {
+            moved: best.0,
+            diagnostics: Diagnostics {
+                depth,
+                nodes: self.nodes.len(),
+                score: best.2,
+                elapsed: start.elapsed(),
+                cut_short: false,
+                tree: None,
+            },
+        }
+    }
+}
+
+/// Moves that do not run into a wall or a body that stays put, or every move
+/// when they are all lethal.
+fn candidates(board: &Board, player: SnakeID) -> Moves {
+    let snake = board.snake(player);
+    if !snake.is_alive() {
+        return smallvec![Move::Up];
+    }
+    let moves: Moves = Move::all()
+        .iter()
+        .copied()
+        .filter(|m| {
+            let pos = m.next(snake.head());
+            match board.get(pos) {
+                Square::Off => false,
+                Square::Snake(s) => {
+                    // Tails move out of the way unless the snake just ate.
+                    let other = board.snake(s);
+                    other.len() > 1
+                        && other.body.tail() == pos
+                        && other.body.get(other.len() - 2) != pos
+                }
+                Square::Food | Square::Empty => true,
+            }
+        })
+        .collect();
+    if moves.is_empty() {
+        return Move::all().iter().copied().collect();
+    }
+    moves
+}
+
+/// Whether a snake at least as long could also move onto `pos`.
+fn contested(board: &Board, player: SnakeID, pos: Pos) -> bool {
+    let len = board.snake(player).len();
+    board.snakes.iter().enumerate().any(|(i, other)| {
+        i != player.0
+            && other.is_alive()
+            && other.len() >= len
+            && Move::all().iter().any(|m| m.next(other.head()) == pos)
+    })
+}
+
+/// Rewards in `0..=1` for every snake: nothing when dead, everything for the
+/// last one standing, otherwise more the longer it is compared to the rest.
+fn rewards(board: &Board) -> Rewards {
+    let alive = board.snakes.iter().filter(|s| s.is_alive()).count();
+    let total: usize = board.snakes.iter().map(|s| s.len()).sum();
+    board
+        .snakes
+        .iter()
+        .map(|snake| {
+            if !snake.is_alive() {
+                0.0
+            } else if alive == 1 && board.snakes.len() > 1 {
+                1.0
+            } else {
+                0.5 + 0.5 * snake.len() as f32 / total as f32
+            }
+        })
+        .collect()
+}
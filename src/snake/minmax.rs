@@ -1,105 +1,281 @@
+use super::strategy::{Decision, Diagnostics, Meta, Strategy};
+use super::super::alloc::{self, Scope, Usage};
+use super::super::stats::STATS;
+use super::table::{Bound, Table};
 use super::*;
+use log::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
 use std::time::Instant;
 
-const INF: f32 = std::f32::INFINITY;
+const INF: f32 = f32::INFINITY;
+/// Transposition table of 2^14 entries.
+const TABLE_BITS: u32 = 14;
+/// Most nodes and edge lists kept as spares, what a larger tree leaves is
+/// freed.
+const MAX_SPARES: usize = 1 << 15;
+/// Spares a helper takes from the pool at once when out of them.
+const REFILL: usize = 64;
 
 pub(super) fn pick(node: &Node) -> Move {
     let mut min = (Move::Up, INF);
     for edge in &node.edges {
-        let edge_score = score(&node, edge);
-        if edge_score < min.1 {
-            min = (edge.moved, edge_score);
+        if edge.score < min.1 {
+            min = (edge.moved, edge.score);
         }
     }
     min.0
 }
 
-fn cost(edge: &Edge) -> f32 {
-    fn will_be_killed_factor(edge: &Edge) -> f32 {
+/// Cost of reaching a node from its parent, lower is better for us.
+fn cost(node: &Node, weights: &Weights) -> f32 {
+    fn escapability_factor(board: &Board) -> f32 {
+        let head = board.snake(SnakeID(0)).head();
         let mut factor = 0.0;
-        for next_edge in &edge.next.edges {
-            if let Result::KillMe = next_edge.next.result {
-                factor += 4.0;
+        for m in Move::all() {
+            if matches!(board.get(m.next(head)), Square::Off | Square::Snake(_)) {
+                factor += 1.0;
             }
         }
         factor
     }
-    fn escapability_factor(edge: &Edge) -> f32 {
+    fn health_factor(board: &Board, weights: &Weights) -> f32 {
+        let snake = board.snake(SnakeID(0));
         let mut factor = 0.0;
-        for next_edge in &edge.next.edges {
-            if next_edge.next.player.is_me()
-                && matches!(next_edge.next.result, Result::Dead | Result::Off)
-            {
-                factor += 1.0;
-            }
+        if board.is_hazard(snake.head()) {
+            factor += weights.hazard * board.game.hazard_damage as f32 / HAZARD_DAMAGE as f32;
+        }
+        if snake.health <= weights.hungry_health {
+            factor += weights.hungry;
         }
         factor
     }
 
-    let mut cost = if edge.next.player.is_me() {
-        match edge.next.result {
-            Result::Off | Result::Dead | Result::KillMe => INF,
-            Result::Eat => -4.0,
-            Result::Kill => -1.0,
-            Result::None => 0.0,
-        }
-    } else {
-        match edge.next.result {
-            Result::Off | Result::Dead => -1.0,
-            Result::None | Result::Kill | Result::Eat => 0.0,
-            Result::KillMe => INF,
-        }
-    };
-    cost += will_be_killed_factor(edge);
-    cost += escapability_factor(edge);
+    if node.results.is_empty() {
+        return 0.0;
+    }
+
+    let mut cost = 0.0;
+    for (i, result) in node.results.iter().enumerate() {
+        cost += if SnakeID(i).is_me() {
+            match result {
+                Result::Off | Result::Dead | Result::Killed | Result::Starve => INF,
+                Result::Eat => weights.eat,
+                Result::Kill => weights.kill,
+                Result::None => 0.0,
+            }
+        } else {
+            match result {
+                Result::Off | Result::Dead | Result::Killed | Result::Starve => weights.death,
+                Result::None | Result::Kill | Result::Eat => 0.0,
+            }
+        };
+    }
+    if cost == INF {
+        return cost;
+    }
+    cost += weights.blocked * escapability_factor(&node.board);
+    if !matches!(node.result(SnakeID(0)), Some(Result::Eat)) {
+        cost += health_factor(&node.board, weights);
+    }
     cost
 }
 
-pub(super) fn score(source: &Node, edge: &Edge) -> f32 {
-    fn minimize(edge: &Edge) -> f32 {
-        let node = &edge.next;
-        let cost = cost(edge);
-        if node.is_leaf() {
-            return cost;
-        }
-
-        let mut min = INF;
-        for edge in &node.edges {
-            let edge_score = score(&node, edge) + cost;
-            if edge_score < min {
-                min = edge_score;
-            }
-        }
-        min
+/// Evaluation of a leaf past the search horizon. Running out of room to
+/// move is nearly as bad as dying, just not certain. With other snakes
+/// around, owning less of the board and its food costs as well.
+fn evaluate(node: &Node, weights: &Weights) -> f32 {
+    let me = node.board.snake(SnakeID(0));
+    if !me.is_alive() {
+        return 0.0;
+    }
+    let mut cost = 0.0;
+    let space = space::flood_fill(&node.board, SnakeID(0));
+    if space < me.len() {
+        cost += weights.trapped + weights.cramped * (me.len() - space) as f32;
     }
+    if node.board.next_player(SnakeID(0)).is_some() {
+        let territory = space::voronoi(&node.board);
+        cost += weights.territory * (1.0 - territory.share(SnakeID(0)));
+        cost += weights.food * territory.food[0].min(weights.food_counted) as f32;
+    }
+    cost
+}
 
-    fn maximize(edge: &Edge) -> f32 {
-        let node = &edge.next;
-        let cost = cost(edge);
-        if node.is_leaf() {
-            return cost;
-        }
+/// The minimax search as a strategy. The transposition table is kept from
+/// one turn to the next, a strategy only ever plays the one game.
+pub(super) struct Minimax {
+    walker: Walker,
+    max_depth: usize,
+}
 
-        let mut max = -INF;
-        for edge in &node.edges {
-            let edge_score = score(&node, edge) + cost;
-            if edge_score > max {
-                max = edge_score;
-            }
+impl Minimax {
+    pub(super) fn new(settings: &Settings) -> Minimax {
+        let mut walker = Walker::new();
+        walker.set_weights(settings.weights);
+        Minimax {
+            walker,
+            max_depth: settings.max_depth,
         }
-        max
     }
+}
+
+impl Strategy for Minimax {
+    fn name(&self) -> &'static str {
+        "minimax"
+    }
+
+    fn decide(&mut self, board: &Board, meta: &Meta) -> Decision {
+        let start = Instant::now();
+        self.walker.set_threads(meta.threads);
+        let node = self.walker.walk(
+            board.clone(),
+            Options {
+                max_depth: self.max_depth,
+                sla: meta.sla,
+            },
+        );
+        debug!("game {} turn {} tree:\n{}", meta.game, meta.turn, node);
+        debug!("transposition hits: {}", self.walker.hits());
 
-    if source.player.is_me() {
-        minimize(edge)
-    } else {
-        maximize(edge)
+        let moved = node.pick();
+        let score = node
+            .edges
+            .iter()
+            .find(|edge| edge.moved == moved)
+            .map_or(INF, |edge| edge.score);
+        let tree = meta.tree_depth.map(|depth| Tree(&node, depth).to_string());
+        self.walker.recycle(node);
+        Decision {
+            moved,
+            diagnostics: Diagnostics {
+                depth: self.walker.depth(),
+                nodes: self.walker.nodes(),
+                score,
+                elapsed: start.elapsed(),
+                cut_short: self.walker.cut_short(),
+                tree,
+            },
+        }
     }
 }
 
+/// Iterative deepening alpha-beta search. Every iteration searches one more
+/// turn and rebuilds the tree, the tree of the last iteration that finished
+/// within the SLA is the one returned. Positions at the start of a turn are
+/// stored in a transposition table, so one reached again through other moves
+/// is not searched twice and later iterations try its best move first.
+///
+/// With more than one thread the moves at the root are split between helper
+/// walkers, each with a transposition table of its own. Their threads are
+/// started once a walk and given every iteration in turn.
+///
+/// Nodes and edge lists of discarded trees are kept as spares and filled in
+/// again by later iterations and walks, so once a walker has built a tree as
+/// large as the one asked for it hardly allocates at all. Helpers take what
+/// they need from a pool of the spares while they search.
 pub(super) struct Walker {
     opts: Options,
     start: Instant,
+    order: [Move; 4],
+    weights: Weights,
+    depth: usize,
+    nodes: usize,
+    cut_short: bool,
+    table: Table,
+    hits: usize,
+    threads: usize,
+    helpers: Vec<Walker>,
+    spare: Spare,
+    /// Spares of a walk on threads, where the helpers take theirs from.
+    pool: Arc<Mutex<Spare>>,
+}
+
+/// Parts of discarded trees, kept for their memory. Edge lists are kept apart
+/// from the nodes since only some nodes get expanded.
+#[derive(Default)]
+struct Spare {
+    nodes: Vec<Node>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl Spare {
+    /// Moves up to `count` nodes and as many edge lists over to `other`.
+    fn lend(&mut self, count: usize, other: &mut Spare) {
+        let at = self.nodes.len().saturating_sub(count);
+        other.nodes.extend(self.nodes.drain(at..));
+        let at = self.edges.len().saturating_sub(count);
+        other.edges.extend(self.edges.drain(at..));
+    }
+
+    /// Moves every spare over to `other`, freeing those past the limit.
+    fn give(&mut self, other: &mut Spare) {
+        let room = MAX_SPARES.saturating_sub(other.nodes.len());
+        other.nodes.extend(self.nodes.drain(..).take(room));
+        let room = MAX_SPARES.saturating_sub(other.edges.len());
+        other.edges.extend(self.edges.drain(..).take(room));
+    }
+}
+
+/// An iteration handed to a helper: how deep to search the root moves and
+/// in which order.
+#[derive(Clone, Copy)]
+struct Job {
+    limit: usize,
+    order: [Move; 4],
+}
+
+/// What a helper reports once out of root moves to search.
+struct Reply {
+    completed: bool,
+    nodes: usize,
+    hits: usize,
+    usage: Usage,
+}
+
+/// What a parallel walk shares with its helpers. The root is only replaced
+/// between iterations, while no helper is searching.
+struct Shared {
+    root: RwLock<Option<Node>>,
+    next: AtomicUsize,
+    searched: Mutex<Vec<(usize, Edge)>>,
+}
+
+impl Shared {
+    fn new() -> Shared {
+        Shared {
+            root: RwLock::new(None),
+            next: AtomicUsize::new(0),
+            searched: Mutex::new(Vec::with_capacity(4)),
+        }
+    }
+}
+
+/// The helpers of a walk, each on a thread of its own waiting for the next
+/// iteration until the pool is dropped.
+struct Pool<'a> {
+    shared: &'a Shared,
+    helpers: Vec<(mpsc::SyncSender<Job>, mpsc::Receiver<Reply>)>,
+}
+
+impl<'a> Pool<'a> {
+    fn start<'env>(
+        scope: &'a thread::Scope<'a, 'env>,
+        shared: &'a Shared,
+        helpers: &'a mut [Walker],
+    ) -> Pool<'a> {
+        let helpers = helpers
+            .iter_mut()
+            .map(|helper| {
+                let (jobs, queue) = mpsc::sync_channel(1);
+                let (done, replies) = mpsc::sync_channel(1);
+                scope.spawn(move || helper.help(shared, queue, done));
+                (jobs, replies)
+            })
+            .collect();
+        Pool { shared, helpers }
+    }
 }
 
 impl Walker {
@@ -107,79 +283,414 @@ impl Walker {
         Walker{
             opts: Options{ max_depth: 0, sla: Duration::from_secs(0) },
             start: Instant::now(),
+            order: Move::all(),
+            weights: Weights::default(),
+            depth: 0,
+            nodes: 0,
+            cut_short: false,
+            table: Table::new(TABLE_BITS),
+            hits: 0,
+            threads: 1,
+            helpers: Vec::new(),
+            spare: Spare::default(),
+            pool: Arc::default(),
         }
     }
 
+    /// Evaluates positions with these weights instead of the defaults.
+    pub(super) fn set_weights(&mut self, weights: Weights) {
+        self.weights = weights;
+    }
+
+    /// Searches on this many threads, one keeps the search deterministic.
+    pub(super) fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Number of turns fully searched by the last walk.
+    pub(super) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Number of nodes expanded by the last walk, over all iterations.
+    pub(super) fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// Whether the last walk ran out of time before the depth limit.
+    pub(super) fn cut_short(&self) -> bool {
+        self.cut_short
+    }
+
+    /// Number of positions the last walk took from the transposition table.
+    pub(super) fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Forgets stored positions, they are kept across walks otherwise.
+    #[cfg(test)]
+    pub(super) fn clear_table(&mut self) {
+        self.table.clear();
+        self.helpers.iter_mut().for_each(Walker::clear_table);
+    }
+
     pub(super) fn walk(&mut self, board: Board, opts: Options) -> Node {
-        let node = Node {
-            board,
-            player: SnakeID(0),
-            edges: Vec::with_capacity(4),
-            result: Result::None,
-        };
+        let players = board.snakes.iter().filter(|s| s.is_alive()).count().max(1);
+        let turns = opts.max_depth.div_ceil(players).max(1);
         self.opts = opts;
         self.start = Instant::now();
-        self.walk_node(node, SnakeID(0), 0)
+        self.order = Move::all();
+        self.depth = 0;
+        self.nodes = 0;
+        self.hits = 0;
+        self.cut_short = false;
+
+        let threads = self.threads.min(self.order.len());
+        let best = if threads > 1 {
+            self.helpers.truncate(threads);
+            while self.helpers.len() < threads {
+                self.helpers.push(Walker::new());
+            }
+            for helper in self.helpers.iter_mut() {
+                helper.opts = self.opts;
+                helper.weights = self.weights;
+                helper.start = self.start;
+                helper.pool = Arc::clone(&self.pool);
+            }
+            let shared = Shared::new();
+            let mut helpers = std::mem::take(&mut self.helpers);
+            let best = thread::scope(|scope| {
+                let pool = Pool::start(scope, &shared, &mut helpers);
+                self.deepen(&board, players, turns, Some(&pool))
+            });
+            self.helpers = helpers;
+            best
+        } else {
+            self.deepen(&board, players, turns, None)
+        };
+
+        // Depth, nodes and time are recorded by the runner for every strategy.
+        if self.cut_short {
+            STATS.search_cut_short.inc();
+        }
+        best
     }
 
-    fn prune(&self, node: &Node, depth: usize) -> bool {
-        (node.player.is_me() && matches!(node.result, Result::Off))
-            || depth >= self.opts.max_depth
-            || self.start.elapsed() >= self.opts.sla
+    /// Searches one more turn at a time until the SLA runs out or `turns`
+    /// are searched, returning the tree of the deepest search completed.
+    fn deepen(&mut self, board: &Board, players: usize, turns: usize, pool: Option<&Pool>) -> Node {
+        let mut best: Option<Node> = None;
+        for turn in 1..=turns {
+            let mut node = self.node(board, smallvec![Move::Up; board.snakes.len()]);
+            let completed = match pool {
+                Some(pool) => {
+                    let (searched, score) = self.search_parallel(pool, node, turn * players);
+                    node = searched;
+                    score.is_some()
+                }
+                None => self.search(&mut node, 0, turn * players, -INF, INF).is_some(),
+            };
+            if completed || best.is_none() {
+                self.order_by(&node);
+                if let Some(previous) = best.replace(node) {
+                    self.recycle(previous);
+                }
+            } else {
+                self.recycle(node);
+            }
+            if !completed {
+                self.cut_short = true;
+                break;
+            }
+            self.depth = turn;
+        }
+        best.unwrap()
     }
 
-    fn walk_node(&mut self, mut node: Node, player: SnakeID, depth: usize) -> Node {
-        if self.prune(&node, depth) {
-            return node;
+    /// Keeps the nodes of a tree as spares for later walks. The tree returned
+    /// by `walk` is best given back once it is no longer needed.
+    pub(super) fn recycle(&mut self, mut node: Node) {
+        while let Some(edge) = node.edges.pop() {
+            self.recycle(edge.next);
         }
-        for m in Move::all() {
-            let next = self.play(&node.board, m, player);
-            let next_edge = Edge {
-                moved: m,
-                next: self.walk_node(next, node.board.next_player(player), depth + 1),
-            };
-            node.edges.push(next_edge);
+        if node.edges.capacity() > 0 && self.spare.edges.len() < MAX_SPARES {
+            self.spare.edges.push(std::mem::take(&mut node.edges));
         }
-        node
+        if self.spare.nodes.len() < MAX_SPARES {
+            self.spare.nodes.push(node);
+        }
+    }
+
+    /// Takes more spares from the pool, for a helper out of them.
+    fn refill(&mut self) {
+        self.pool.lock().unwrap().lend(REFILL, &mut self.spare);
     }
 
-    fn play(&mut self, board: &Board, m: Move, player: SnakeID) -> Node {
-        let head = board.snake(player).head();
-        let next_head = m.next(head);
-        let mut next_board = board.clone();
-        let mut next_snake = next_board.snake(player).clone();
+    /// An empty edge list, a spare one when there is any.
+    fn edges(&mut self) -> Vec<Edge> {
+        if self.spare.edges.is_empty() {
+            self.refill();
+        }
+        self.spare.edges.pop().unwrap_or_default()
+    }
 
-        let result = match next_board.get(next_head) {
-            Square::Off => Result::Off,
-            Square::Food => {
-                next_board.remove_food(next_head);
-                Result::Eat
-            }
-            Square::Empty => {
-                next_snake.remove_tail();
-                Result::None
+    /// A node for `board` with no edges, a spare one when there is any.
+    fn node(&mut self, board: &Board, moves: Moves) -> Node {
+        if self.spare.nodes.is_empty() {
+            self.refill();
+        }
+        match self.spare.nodes.pop() {
+            Some(mut node) => {
+                node.board.clone_from(board);
+                node.player = SnakeID(0);
+                node.moves = moves;
+                node.results.clear();
+                node
             }
-            Square::Snake(s) => {
-                let snake = next_board.snake(s);
-                if next_snake.len() > snake.len() && next_head == snake.head() {
-                    if s.is_me() {
-                        Result::KillMe
-                    } else {
-                        Result::Kill
+            None => Node {
+                board: board.clone(),
+                edges: Vec::new(),
+                player: SnakeID(0),
+                moves,
+                results: Results::new(),
+            },
+        }
+    }
+
+    /// Tries the best moves of the previous iteration first at the root,
+    /// which is where most of the cutoffs come from. Moves not searched go
+    /// last.
+    fn order_by(&mut self, node: &Node) {
+        let score = |m: &Move| {
+            node.edges
+                .iter()
+                .find(|edge| edge.moved == *m)
+                .map_or(INF, |edge| edge.score)
+        };
+        // The edges were searched in the current order, a stable sort keeps
+        // it between equal scores.
+        self.order.sort_by(|a, b| score(a).total_cmp(&score(b)));
+    }
+
+    /// Searches the root moves on the helper threads, each taking the next
+    /// move not searched yet. The root loses the bound the moves searched
+    /// before would give, the subtrees are independent otherwise.
+    fn search_parallel(&mut self, pool: &Pool, mut node: Node, limit: usize) -> (Node, Option<f32>) {
+        let shared = pool.shared;
+        // Subtrees come back to this walker, the helpers take its spares.
+        std::mem::swap(&mut self.spare, &mut *self.pool.lock().unwrap());
+        *shared.root.write().unwrap() = Some(node);
+        shared.next.store(0, Ordering::Relaxed);
+        for (jobs, _) in &pool.helpers {
+            let job = Job {
+                limit,
+                order: self.order,
+            };
+            jobs.send(job).expect("search helper panicked");
+        }
+        // The helpers allocate on behalf of this thread.
+        let mut completed = true;
+        let mut usage = Usage::default();
+        for (_, replies) in &pool.helpers {
+            let reply = replies.recv().expect("search helper panicked");
+            completed &= reply.completed;
+            self.nodes += reply.nodes;
+            self.hits += reply.hits;
+            usage += reply.usage;
+        }
+        alloc::absorb(usage);
+        std::mem::swap(&mut self.spare, &mut *self.pool.lock().unwrap());
+
+        node = shared.root.write().unwrap().take().unwrap();
+        self.nodes += 1;
+        if node.edges.capacity() == 0 {
+            node.edges = self.edges();
+        }
+        let mut searched = shared.searched.lock().unwrap();
+        searched.sort_unstable_by_key(|(i, _)| *i);
+        node.edges.extend(searched.drain(..).map(|(_, edge)| edge));
+        drop(searched);
+        if !completed {
+            return (node, None);
+        }
+        let score = node.edges.iter().map(|edge| edge.score).fold(INF, f32::min);
+        (node, Some(score))
+    }
+
+    /// Runs on a helper thread for a whole walk, searching root moves for
+    /// every iteration until the walk is over.
+    fn help(&mut self, shared: &Shared, jobs: mpsc::Receiver<Job>, replies: mpsc::SyncSender<Reply>) {
+        for job in jobs {
+            let allocations = Scope::start();
+            let (nodes, hits) = (self.nodes, self.hits);
+            let guard = shared.root.read().unwrap();
+            let root = guard.as_ref().unwrap();
+            let mut beta = INF;
+            let completed = loop {
+                let i = shared.next.fetch_add(1, Ordering::Relaxed);
+                if i >= job.order.len() {
+                    break true;
+                }
+                let mut edge = Edge {
+                    next: self.play(root, job.order[i]),
+                    moved: job.order[i],
+                    score: INF,
+                };
+                let cost = cost(&edge.next, &self.weights);
+                if cost != INF {
+                    match self.search(&mut edge.next, 1, job.limit, -INF, beta - cost) {
+                        Some(score) => edge.score = cost + score,
+                        None => {
+                            self.recycle(edge.next);
+                            break false;
+                        }
                     }
-                } else {
-                    Result::Dead
                 }
+                beta = beta.min(edge.score);
+                shared.searched.lock().unwrap().push((i, edge));
+            };
+            drop(guard);
+            // Whatever is left goes back for the others to take.
+            self.spare.give(&mut self.pool.lock().unwrap());
+            let reply = Reply {
+                completed,
+                nodes: self.nodes - nodes,
+                hits: self.hits - hits,
+                usage: allocations.usage(),
+            };
+            if replies.send(reply).is_err() {
+                return;
             }
+        }
+    }
+
+    /// Stops at the depth limit once the current turn is resolved, or as soon
+    /// as we are out of the game.
+    fn is_leaf(&self, node: &Node, depth: usize, limit: usize) -> bool {
+        matches!(node.result(SnakeID(0)), Some(result) if result.is_dead())
+            || (depth >= limit && node.player.is_me())
+    }
+
+    /// Expands the node and returns its score, or `None` when the SLA ran out
+    /// before the subtree was searched.
+    fn search(
+        &mut self,
+        node: &mut Node,
+        depth: usize,
+        limit: usize,
+        mut alpha: f32,
+        mut beta: f32,
+    ) -> Option<f32> {
+        if self.is_leaf(node, depth, limit) {
+            return Some(evaluate(node, &self.weights));
+        }
+        if self.start.elapsed() >= self.opts.sla {
+            return None;
+        }
+
+        // Only whole turns are stored, the root needs its edges.
+        let key = if depth > 0 && node.player.is_me() {
+            Some(zobrist::hash(&node.board))
+        } else {
+            None
         };
+        let remaining = (limit - depth) as u16;
+        let mut moves = if depth == 0 { self.order } else { Move::all() };
+        if let Some(entry) = key.and_then(|key| self.table.get(key).copied()) {
+            if entry.depth >= remaining {
+                let hit = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => entry.score >= beta,
+                    Bound::Upper => entry.score <= alpha,
+                };
+                if hit {
+                    self.hits += 1;
+                    return Some(entry.score);
+                }
+            }
+            moves = first(entry.best);
+        }
+        self.nodes += 1;
+
+        let window = (alpha, beta);
+        let maximize = !node.player.is_me();
+        let mut best = if maximize { -INF } else { INF };
+        let mut best_move = moves[0];
+        if node.edges.capacity() == 0 {
+            node.edges = self.edges();
+        }
+        node.edges.reserve_exact(moves.len());
+        for m in moves {
+            let next = self.play(node, m);
+            node.edges.push(Edge {
+                next,
+                moved: m,
+                score: INF,
+            });
+            let edge = node.edges.last_mut().unwrap();
+            let cost = cost(&edge.next, &self.weights);
+            if cost != INF {
+                edge.score =
+                    cost + self.search(&mut edge.next, depth + 1, limit, alpha - cost, beta - cost)?;
+            }
+            let score = edge.score;
 
-        next_snake.change_head(next_head);
-        next_board.set_snake(player, next_snake);
-        Node {
-            player,
-            edges: Vec::with_capacity(4),
-            result,
-            board: next_board,
+            if maximize {
+                best = best.max(score);
+                alpha = alpha.max(score);
+            } else {
+                if score < best {
+                    best = score;
+                    best_move = m;
+                }
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let Some(key) = key {
+            let bound = if best <= window.0 {
+                Bound::Upper
+            } else if best >= window.1 {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.table.put(key, remaining, bound, best, best_move);
         }
+        Some(best)
     }
+
+    fn play(&mut self, node: &Node, m: Move) -> Node {
+        let mut next = self.node(&node.board, node.moves.clone());
+        next.moves[node.player.0] = m;
+
+        // Walls do not depend on the other snakes, so running into one ends
+        // the turn right away.
+        let off = node.player.is_me()
+            && matches!(node.board.get(m.next(node.board.snake(node.player).head())), Square::Off);
+        if let (false, Some(player)) = (off, node.board.next_player(node.player)) {
+            next.player = player;
+            return next;
+        }
+
+        next.results = if off {
+            let mut results: Results = smallvec![Result::None; next.moves.len()];
+            results[0] = Result::Off;
+            results
+        } else {
+            next.board.apply_moves(&next.moves)
+        };
+        next
+    }
+}
+
+/// All moves, `m` first.
+fn first(m: Move) -> [Move; 4] {
+    let mut moves = Move::all();
+    let i = moves.iter().position(|other| *other == m).unwrap();
+    moves[..=i].rotate_right(1);
+    moves
 }
@@ -1,16 +1,34 @@
 mod api;
+mod bits;
+mod clock;
+mod greedy;
+mod mcts;
 mod minmax;
+mod rng;
+mod session;
+pub mod sim;
+mod space;
+mod strategy;
+mod table;
+mod zobrist;
 
 #[cfg(test)]
 mod tests;
 
-pub use api::Runner;
+pub use api::{fallback, Runner};
+pub use strategy::Diagnostics;
+use bits::{BitSet, Body};
+use serde::{Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
 use std::fmt;
 use std::time::Duration;
 
-type Pos = (isize, isize);
+type Pos = (i8, i8);
 
-#[derive(Debug, Clone, Copy)]
+const MAX_HEALTH: isize = 100;
+const HAZARD_DAMAGE: isize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Move {
     Up,
     Down,
@@ -33,16 +51,33 @@ impl Move {
     }
 }
 
-#[derive(Debug)]
+/// Outcome of a turn for a single snake.
+#[derive(Clone, Copy, Debug)]
 enum Result {
     None,
     Eat,
+    /// Ran into a body.
     Dead,
+    /// Won a head to head collision.
     Kill,
-    KillMe,
+    /// Lost a head to head collision.
+    Killed,
     Off,
+    Starve,
 }
 
+impl Result {
+    fn is_dead(&self) -> bool {
+        matches!(
+            self,
+            Result::Dead | Result::Killed | Result::Off | Result::Starve
+        )
+    }
+}
+
+type Moves = SmallVec<[Move; 4]>;
+type Results = SmallVec<[Result; 4]>;
+
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -71,14 +106,37 @@ enum Square {
     Off,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct Snake {
-    body: Vec<Pos>,
+    body: Body,
+    /// Squares covered by the body, kept in sync by the board.
+    squares: BitSet,
+    health: isize,
+}
+
+impl Clone for Snake {
+    fn clone(&self) -> Snake {
+        Snake {
+            body: self.body.clone(),
+            squares: self.squares,
+            health: self.health,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Snake) {
+        self.body.clone_from(&source.body);
+        self.squares = source.squares;
+        self.health = source.health;
+    }
 }
 
 impl Snake {
     fn head(&self) -> Pos {
-        self.body[0]
+        self.body.head()
+    }
+
+    fn is_alive(&self) -> bool {
+        !self.body.is_empty()
     }
 
     fn len(&self) -> usize {
@@ -86,68 +144,148 @@ impl Snake {
     }
 
     fn new(body: Vec<Pos>) -> Snake {
-        Snake { body }
+        Snake {
+            body: Body::new(body),
+            squares: BitSet::new(),
+            health: MAX_HEALTH,
+        }
     }
 
-    fn remove_tail(&mut self) {
-        self.body.pop();
+    fn with_health(mut self, health: isize) -> Snake {
+        self.health = health;
+        self
     }
 
-    fn change_head(&mut self, pos: Pos) {
-        self.body.insert(0, pos);
+    fn eliminate(&mut self) {
+        self.body.clear();
+        self.squares.clear();
     }
 }
 
 #[derive(Clone, Debug)]
 struct Game {
-    width: isize,
-    height: isize,
+    width: i8,
+    height: i8,
+    hazard_damage: isize,
+    max_health: isize,
 }
 
-#[derive(Clone, Debug)]
+impl Game {
+    fn new(width: i8, height: i8) -> Game {
+        assert!(
+            Game::fits(width as u16, height as u16),
+            "unsupported board size {}x{}",
+            width,
+            height
+        );
+        Game {
+            width,
+            height,
+            hazard_damage: HAZARD_DAMAGE,
+            max_health: MAX_HEALTH,
+        }
+    }
+
+    /// Whether a board of this size fits in the board bitsets.
+    fn fits(width: u16, height: u16) -> bool {
+        (1..=i8::MAX as u16).contains(&width)
+            && (1..=i8::MAX as u16).contains(&height)
+            && width as usize * height as usize <= BitSet::CAPACITY
+    }
+
+    /// Index of the square in the board bitsets, `None` when off the board.
+    fn index(&self, pos: Pos) -> Option<usize> {
+        if pos.0 < 0 || pos.1 < 0 || pos.0 >= self.width || pos.1 >= self.height {
+            return None;
+        }
+        Some(pos.1 as usize * self.width as usize + pos.0 as usize)
+    }
+
+    fn pos(&self, i: usize) -> Pos {
+        (
+            (i % self.width as usize) as i8,
+            (i / self.width as usize) as i8,
+        )
+    }
+}
+
+#[derive(Debug)]
 struct Board {
     game: Game,
-    snakes: Vec<Snake>,
-    food: Vec<Pos>,
+    snakes: SmallVec<[Snake; 4]>,
+    food: BitSet,
+    hazards: BitSet,
+}
+
+/// Copying into an existing board reuses the memory of its snakes, which is
+/// how the search keeps from allocating a board per node.
+impl Clone for Board {
+    fn clone(&self) -> Board {
+        Board {
+            game: self.game.clone(),
+            snakes: self.snakes.clone(),
+            food: self.food,
+            hazards: self.hazards,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Board) {
+        self.game.clone_from(&source.game);
+        self.snakes.truncate(source.snakes.len());
+        let kept = self.snakes.len();
+        for (snake, other) in self.snakes.iter_mut().zip(&source.snakes) {
+            snake.clone_from(other);
+        }
+        self.snakes.extend(source.snakes[kept..].iter().cloned());
+        self.food = source.food;
+        self.hazards = source.hazards;
+    }
 }
 
 impl Board {
-    // fn default() -> Board {
-    //     Board {
-    //         game: Game{ width: 0, height: 0 },
-    //         snakes: Vec::with_capacity(10),
-    //         food: Vec::with_capacity(10),
-    //     }
-    // }
-
-    fn new(game: Game, snakes: Vec<Snake>, food: Vec<Pos>) -> Board {
-        Board{
+    fn new(game: Game, snakes: Vec<Snake>, food: Vec<Pos>, hazards: Vec<Pos>) -> Board {
+        let mut board = Board {
             game,
-            snakes: snakes,
-            food: food,
+            snakes: SmallVec::from_vec(snakes),
+            food: BitSet::new(),
+            hazards: BitSet::new(),
+        };
+        for pos in food {
+            if let Some(i) = board.game.index(pos) {
+                board.food.set(i);
+            }
         }
+        for pos in hazards {
+            if let Some(i) = board.game.index(pos) {
+                board.hazards.set(i);
+            }
+        }
+        for snake in board.snakes.iter_mut() {
+            snake.squares.clear();
+            for pos in snake.body.iter() {
+                if let Some(i) = board.game.index(pos) {
+                    snake.squares.set(i);
+                }
+            }
+        }
+        board
     }
 
-    fn remove_food(&mut self, pos: Pos) {
-        if let Some(idx) = self.food.iter().position(|food| *food == pos) {
-            self.food.remove(idx);
-        }
+    fn is_hazard(&self, pos: Pos) -> bool {
+        self.game.index(pos).is_some_and(|i| self.hazards.has(i))
     }
 
     fn get(&self, pos: Pos) -> Square {
-        if pos.0 < 0 || pos.1 < 0 || pos.0 >= self.game.width || pos.1 >= self.game.height {
-            return Square::Off;
+        let i = match self.game.index(pos) {
+            Some(i) => i,
+            None => return Square::Off,
         };
-        for food in self.food.iter() {
-            if *food == pos {
-                return Square::Food;
-            };
+        if self.food.has(i) {
+            return Square::Food;
         }
-        for (i, snake) in self.snakes.iter().enumerate() {
-            for point in snake.body.iter() {
-                if *point == pos {
-                    return Square::Snake(SnakeID(i));
-                }
+        for (id, snake) in self.snakes.iter().enumerate() {
+            if snake.squares.has(i) {
+                return Square::Snake(SnakeID(id));
             }
         }
         Square::Empty
@@ -157,15 +295,104 @@ impl Board {
         &self.snakes[player.0]
     }
 
-    fn set_snake(&mut self, player: SnakeID, snake: Snake) {
-        self.snakes[player.0] = snake;
+    /// The next living snake to pick a move this turn, if any.
+    fn next_player(&self, player: SnakeID) -> Option<SnakeID> {
+        (player.0 + 1..self.snakes.len())
+            .find(|i| self.snakes[*i].is_alive())
+            .map(SnakeID)
     }
 
-    fn next_player(&self, player: SnakeID) -> SnakeID {
-        if player.0 + 1 >= self.snakes.len() {
-            return SnakeID(0);
+    /// Resolves one turn where every snake moves at once, in the order of
+    /// the standard ruleset: move, reduce health, feed, eliminate. Moves of
+    /// snakes that are already eliminated are ignored.
+    fn apply_moves(&mut self, moves: &[Move]) -> Results {
+        let mut results: Results = smallvec![Result::None; self.snakes.len()];
+        let game = &self.game;
+
+        for (snake, m) in self.snakes.iter_mut().zip(moves) {
+            if !snake.is_alive() {
+                continue;
+            }
+            let tail = snake.body.advance(m.next(snake.head()));
+            if snake.body.tail() != tail {
+                if let Some(i) = game.index(tail) {
+                    snake.squares.unset(i);
+                }
+            }
+            snake.health -= 1;
+        }
+
+        // Every square still set is a body segment, heads are added after.
+        let mut crashed: SmallVec<[bool; 4]> = smallvec![false; self.snakes.len()];
+        for (snake, crashed) in self.snakes.iter().zip(crashed.iter_mut()) {
+            if let Some(i) = snake.body.first().and_then(|head| game.index(head)) {
+                *crashed = self.snakes.iter().any(|other| other.squares.has(i));
+            }
+        }
+
+        for (i, snake) in self.snakes.iter_mut().enumerate() {
+            let head = match snake.body.first().and_then(|head| game.index(head)) {
+                Some(head) => head,
+                None => continue,
+            };
+            snake.squares.set(head);
+            if self.food.has(head) {
+                snake.health = game.max_health;
+                snake.body.grow();
+                results[i] = Result::Eat;
+            } else if self.hazards.has(head) {
+                snake.health -= game.hazard_damage;
+            }
         }
-        return SnakeID(player.0 + 1);
+        for snake in &self.snakes {
+            if let Some(i) = snake.body.first().and_then(|head| game.index(head)) {
+                self.food.unset(i);
+            }
+        }
+
+        for (i, result) in results.iter_mut().enumerate() {
+            if let Some(collision) = self.collision(SnakeID(i), crashed[i]) {
+                *result = collision;
+            }
+        }
+
+        for (snake, result) in self.snakes.iter_mut().zip(&results) {
+            if result.is_dead() {
+                snake.eliminate();
+            }
+        }
+        results
+    }
+
+    /// Checks a snake that just moved against the walls, its health and
+    /// the heads of every other snake. `crashed` is set when the head landed
+    /// on a body segment.
+    fn collision(&self, player: SnakeID, crashed: bool) -> Option<Result> {
+        let snake = self.snake(player);
+        if !snake.is_alive() {
+            return None;
+        }
+        let head = snake.head();
+        if self.game.index(head).is_none() {
+            return Some(Result::Off);
+        }
+        if snake.health <= 0 {
+            return Some(Result::Starve);
+        }
+        if crashed {
+            return Some(Result::Dead);
+        }
+        let mut result = None;
+        for (i, other) in self.snakes.iter().enumerate() {
+            if i == player.0 || !other.is_alive() || other.head() != head {
+                continue;
+            }
+            if snake.len() <= other.len() {
+                return Some(Result::Killed);
+            }
+            result = Some(Result::Kill);
+        }
+        result
     }
 }
 
@@ -173,19 +400,28 @@ impl Board {
 struct Edge {
     next: Node,
     moved: Move,
+    /// Searched score, a bound rather than the exact value when the edge
+    /// caused a cutoff.
+    score: f32,
 }
 
+/// A position in the search tree. Snakes pick their moves one after the
+/// other, `player` being the one choosing the edges out of this node, and the
+/// board only changes once the last living snake has picked.
 #[derive(Debug)]
 struct Node {
     board: Board,
     edges: Vec<Edge>,
     player: SnakeID,
-    result: Result,
+    /// Moves picked so far this turn, indexed by snake.
+    moves: Moves,
+    /// Outcome for every snake if this node resolved a turn, empty otherwise.
+    results: Results,
 }
 
 impl Node {
-    fn is_leaf(&self) -> bool {
-        self.edges.is_empty()
+    fn result(&self, player: SnakeID) -> Option<Result> {
+        self.results.get(player.0).copied()
     }
 
     fn pick(&self) -> Move {
@@ -193,11 +429,86 @@ impl Node {
     }
 }
 
+#[derive(Clone, Copy)]
 struct Options {
+    /// Number of moves to look ahead, rounded up to whole turns.
     max_depth: usize,
     sla: Duration,
 }
 
+/// Weights of the minimax evaluation. Costs are added up along the searched
+/// moves, lower is better for us, so rewards are negative.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    /// Eating food.
+    pub eat: f32,
+    /// Winning a head to head collision.
+    pub kill: f32,
+    /// Another snake dying.
+    pub death: f32,
+    /// Every square around our head we cannot move to.
+    pub blocked: f32,
+    /// Ending a move in a hazard of standard damage, scaled with the damage.
+    pub hazard: f32,
+    /// Ending a move at or below `hungry_health` without eating.
+    pub hungry: f32,
+    pub hungry_health: isize,
+    /// Being left with less room than our length at the search horizon.
+    pub trapped: f32,
+    /// Every square of room short of our length on top of that.
+    pub cramped: f32,
+    /// Share of the board owned by other snakes at the search horizon.
+    pub territory: f32,
+    /// Every food we are closer to than anybody else, up to `food_counted`.
+    pub food: f32,
+    pub food_counted: usize,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            eat: -4.0,
+            kill: -1.0,
+            death: -1.0,
+            blocked: 1.0,
+            // As much as two blocked squares.
+            hazard: 2.0,
+            hungry: 1.0,
+            hungry_health: 25,
+            trapped: 20.0,
+            cramped: 1.0,
+            territory: 2.0,
+            food: -0.5,
+            food_counted: 4,
+        }
+    }
+}
+
+/// Search limits and weights the strategies are built with, and the rules
+/// the engine does not send.
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    /// Moves the minimax search looks ahead at most.
+    pub max_depth: usize,
+    /// Moves a Monte Carlo playout runs for at most.
+    pub playout_depth: usize,
+    /// Health snakes eat back up to, for rules other than the standard ones.
+    pub max_health: u16,
+    pub weights: Weights,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            max_depth: 20,
+            playout_depth: 20,
+            max_health: MAX_HEALTH as u16,
+            weights: Weights::default(),
+        }
+    }
+}
+
 impl fmt::Display for Board {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for row in 0..self.game.height {
@@ -207,17 +518,27 @@ impl fmt::Display for Board {
                 match self.get((x, y)) {
                     Square::Snake(i) => write!(f, "{} ", i.0)?,
                     Square::Food => write!(f, "F ")?,
+                    Square::Empty if self.is_hazard((x, y)) => write!(f, "H ")?,
                     Square::Empty => write!(f, "_ ")?,
                     Square::Off => unreachable!(),
                 }
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
 }
 
 impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Tree(self, usize::MAX).fmt(f)
+    }
+}
+
+/// A search tree printed down to a number of moves from the root.
+struct Tree<'a>(&'a Node, usize);
+
+impl fmt::Display for Tree<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fn pprint_tree(
             f: &mut fmt::Formatter,
@@ -225,36 +546,39 @@ impl fmt::Display for Node {
             name: String,
             prefix: String,
             last: bool,
+            depth: usize,
         ) -> fmt::Result {
             let prefix_current = "|- ";
 
-            write!(f, "{}{}{}\n", prefix, prefix_current, name)?;
+            writeln!(f, "{}{}{}", prefix, prefix_current, name)?;
 
             let prefix_child = if last { "   " } else { "|  " };
             let prefix = prefix + prefix_child;
 
-            if !node.edges.is_empty() {
+            if !node.edges.is_empty() && depth > 0 {
                 let last_child = node.edges.len() - 1;
 
                 for (i, child) in node.edges.iter().enumerate() {
                     let name = format!(
                         "{} [{}] {}",
                         child.moved,
-                        child.next.player.0,
-                        minmax::score(node, child),
+                        node.player.0,
+                        child.score,
                     );
-                    pprint_tree(f, &child.next, name, prefix.to_string(), i == last_child)?;
+                    pprint_tree(f, &child.next, name, prefix.to_string(), i == last_child, depth - 1)?;
                 }
             }
             Ok(())
         }
 
+        let Tree(root, depth) = *self;
         pprint_tree(
             f,
-            self,
-            format!("Root [{}] {}", self.player.0, self.pick()),
+            root,
+            format!("Root [{}] {}", root.player.0, root.pick()),
             "".to_string(),
             true,
+            depth,
         )
     }
 }
@@ -0,0 +1,145 @@
+use super::super::api;
+use super::super::stats::STATS;
+use super::clock::Clock;
+use super::strategy::Strategy;
+use super::*;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Sessions of games without a move for this long are dropped, their `/end`
+/// never came.
+const ABANDONED: Duration = Duration::from_secs(600);
+
+/// State kept between turns of a single game, created on `/start` and
+/// dropped on `/end`. The strategies are the game's own, so search state
+/// like the transposition table carries over whichever worker takes a turn.
+pub(super) struct Session {
+    pub(super) turn: u16,
+    pub(super) previous: Option<Board>,
+    pub(super) heads: HashMap<String, Vec<Pos>>,
+    pub(super) clock: Clock,
+    pub(super) strategies: HashMap<&'static str, Box<dyn Strategy>>,
+    /// When the game was last heard of.
+    seen: Instant,
+}
+
+impl Session {
+    pub(super) fn new(margin: Duration) -> Session {
+        Session {
+            turn: 0,
+            previous: None,
+            heads: HashMap::new(),
+            clock: Clock::new(margin),
+            strategies: HashMap::new(),
+            seen: Instant::now(),
+        }
+    }
+
+    /// Records the head of every snake still on the board, building up the
+    /// move history of each opponent over the game.
+    pub(super) fn observe(&mut self, req: &api::MoveRequest) {
+        self.turn = req.turn;
+        self.seen = Instant::now();
+        self.clock.observe(req.you.latency_ms());
+        for snake in &req.board.snakes {
+            if let Some(head) = snake.body.first() {
+                self.heads
+                    .entry(snake.id.to_string())
+                    .or_default()
+                    .push(super::api::pos(head));
+            }
+        }
+    }
+
+    /// Moves observed for the given snake, derived from consecutive heads.
+    pub(super) fn moves(&self, id: &str) -> Vec<Move> {
+        let heads = match self.heads.get(id) {
+            Some(heads) => heads,
+            None => return Vec::new(),
+        };
+        heads
+            .windows(2)
+            .filter_map(|w| Move::all().iter().copied().find(|m| m.next(w[0]) == w[1]))
+            .collect()
+    }
+}
+
+/// Sessions of the games in progress, shared by the runners of every worker.
+/// A session is taken out while its move is picked, the lock is never held
+/// during a search. The games gauge follows the number of sessions.
+#[derive(Clone, Default)]
+pub(super) struct Sessions(Arc<Mutex<HashMap<String, Session>>>);
+
+impl Sessions {
+    /// Stores the session of a game, returning the one it replaces. Sessions
+    /// of abandoned games are dropped on the way.
+    pub(super) fn insert(&self, id: &str, session: Session) -> Option<Session> {
+        let mut sessions = self.0.lock().unwrap();
+        sessions.retain(|_, session| session.seen.elapsed() < ABANDONED);
+        let replaced = sessions.insert(id.to_string(), session);
+        STATS.games.set(sessions.len() as isize);
+        replaced
+    }
+
+    pub(super) fn take(&self, id: &str) -> Option<Session> {
+        let mut sessions = self.0.lock().unwrap();
+        let session = sessions.remove(id);
+        STATS.games.set(sessions.len() as isize);
+        session
+    }
+
+    /// Takes out the session of a game for a move, or the one `new` gives
+    /// when there is none. It is put back once the move is picked or panics.
+    pub(super) fn lend<'a, F>(&'a self, id: &'a str, new: F) -> Lent<'a>
+    where
+        F: FnOnce() -> Session,
+    {
+        let session = self.take(id).unwrap_or_else(new);
+        Lent {
+            sessions: self,
+            id,
+            session: Some(session),
+        }
+    }
+
+    /// Puts a session back unless a later turn put back one of its own
+    /// meanwhile, which happens when a move is given up on and runs late.
+    fn put_back(&self, id: &str, session: Session) {
+        let mut sessions = self.0.lock().unwrap();
+        if sessions.get(id).is_none_or(|other| other.turn < session.turn) {
+            sessions.insert(id.to_string(), session);
+            STATS.games.set(sessions.len() as isize);
+        }
+    }
+}
+
+/// A session lent out for a move, put back when dropped.
+pub(super) struct Lent<'a> {
+    sessions: &'a Sessions,
+    id: &'a str,
+    session: Option<Session>,
+}
+
+impl Deref for Lent<'_> {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        self.session.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for Lent<'_> {
+    fn deref_mut(&mut self) -> &mut Session {
+        self.session.as_mut().unwrap()
+    }
+}
+
+impl Drop for Lent<'_> {
+    fn drop(&mut self) {
+        if let Some(session) = self.session.take() {
+            self.sessions.put_back(self.id, session);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use super::super::{api, http};
+use super::rng::Rng;
+use super::*;
+use log::*;
+use std::io;
+use std::time::Instant;
+
+/// Settings of a simulated game under the standard ruleset.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub width: u16,
+    pub height: u16,
+    /// Chance in percent of a food spawning on a turn with enough food.
+    pub food_spawn_chance: u16,
+    /// Food kept on the board at the least.
+    pub minimum_food: u16,
+    /// Time a snake has to answer, in milliseconds.
+    pub timeout: u16,
+    /// Turns after which a game still going is a draw.
+    pub max_turns: u16,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            width: 11,
+            height: 11,
+            food_spawn_chance: 15,
+            minimum_food: 1,
+            timeout: 500,
+            max_turns: 1000,
+        }
+    }
+}
+
+impl Rules {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.width == 0 || self.height == 0 || self.width > 25 || self.height > 25 {
+            return Err("width and height must be between 1 and 25".into());
+        }
+        if self.food_spawn_chance > 100 {
+            return Err("food_spawn_chance must be at most 100".into());
+        }
+        Ok(())
+    }
+}
+
+/// A snake taking part in a simulated game, sent the requests the engine
+/// would send it.
+pub trait Player {
+    fn start(&mut self, req: &api::MoveRequest);
+
+    /// The move for the turn of `req`, or why there is none.
+    fn play(&mut self, req: &api::MoveRequest) -> std::result::Result<api::Direction, String>;
+
+    fn end(&mut self, req: &api::MoveRequest);
+}
+
+impl Player for Runner {
+    fn start(&mut self, req: &api::MoveRequest) {
+        Runner::start(self, req);
+    }
+
+    fn play(&mut self, req: &api::MoveRequest) -> std::result::Result<api::Direction, String> {
+        Ok(self.run(req).0)
+    }
+
+    fn end(&mut self, req: &api::MoveRequest) {
+        Runner::end(self, req);
+    }
+}
+
+/// A snake served over HTTP, like one the engine plays against.
+pub struct Remote {
+    addr: String,
+    /// Prefix of the endpoint paths.
+    path: String,
+}
+
+impl Remote {
+    /// Takes a URL like `http://127.0.0.1:3000`, only plain HTTP is spoken.
+    pub fn new(url: &str) -> std::result::Result<Remote, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("{}: only http:// URLs are supported", url))?;
+        let (addr, path) = match rest.find('/') {
+            Some(at) => rest.split_at(at),
+            None => (rest, ""),
+        };
+        if addr.is_empty() {
+            return Err(format!("{}: missing host", url));
+        }
+        Ok(Remote {
+            addr: addr.to_string(),
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn post(&self, endpoint: &str, req: &api::MoveRequest) -> io::Result<Vec<u8>> {
+        let body = serde_json::to_vec(req)?;
+        let path = format!("{}{}", self.path, endpoint);
+        let timeout = Duration::from_millis(req.game.timeout.max(1) as u64);
+        let (code, body) = http::post(&self.addr, &path, &body, timeout)?;
+        if code != 200 {
+            let err = format!("{} answered {}", path, code);
+            return Err(io::Error::other(err));
+        }
+        Ok(body)
+    }
+}
+
+impl Player for Remote {
+    fn start(&mut self, req: &api::MoveRequest) {
+        if let Err(err) = self.post("/start", req) {
+            warn!("{}: {}", self.addr, err);
+        }
+    }
+
+    fn play(&mut self, req: &api::MoveRequest) -> std::result::Result<api::Direction, String> {
+        let body = self.post("/move", req).map_err(|err| err.to_string())?;
+        let res: api::MoveResponse =
+            serde_json::from_slice(&body).map_err(|err| err.to_string())?;
+        Ok(res.direction)
+    }
+
+    fn end(&mut self, req: &api::MoveRequest) {
+        if let Err(err) = self.post("/end", req) {
+            warn!("{}: {}", self.addr, err);
+        }
+    }
+}
+
+/// How a simulated game went.
+#[derive(Debug)]
+pub struct Outcome {
+    /// The last snake standing, by index. Solo games and draws have none.
+    pub winner: Option<usize>,
+    pub turns: u16,
+    /// Turn on which each snake was eliminated, `None` for the ones left.
+    pub eliminated: Vec<Option<u16>>,
+    /// Every `/move` request sent, in order, as JSON.
+    pub log: Vec<String>,
+    /// The board at the end of the game.
+    pub board: String,
+}
+
+/// Plays a game of `players` to its end. Snakes are named `snake-0` and so
+/// on in the order given, the board and food are drawn from `seed`. A snake
+/// failing to answer keeps going the way it last moved, like the engine has
+/// it.
+pub fn play(id: &str, rules: &Rules, seed: u64, players: &mut [Box<dyn Player>]) -> Outcome {
+    let mut rng = Rng::new(seed);
+    let mut board = setup(rules, players.len(), &mut rng);
+    let ids: Vec<String> = (0..players.len()).map(|i| format!("snake-{}", i)).collect();
+    let mut latency = vec![String::new(); players.len()];
+    let mut moves: Moves = smallvec![Move::Up; players.len()];
+    let mut eliminated = vec![None; players.len()];
+    let mut log = Vec::new();
+
+    let mut turn = 0;
+    for (i, player) in players.iter_mut().enumerate() {
+        player.start(&request(id, rules, turn, &board, &ids, &latency, i));
+    }
+    while !is_over(&board) && turn < rules.max_turns {
+        for (i, player) in players.iter_mut().enumerate() {
+            if !board.snakes[i].is_alive() {
+                continue;
+            }
+            let req = request(id, rules, turn, &board, &ids, &latency, i);
+            log.push(serde_json::to_string(&req).unwrap());
+            let start = Instant::now();
+            match player.play(&req) {
+                Ok(direction) => moves[i] = to_move(&direction),
+                Err(err) => warn!("game {} turn {}: {} failed: {}", id, turn, ids[i], err),
+            }
+            latency[i] = start.elapsed().as_millis().to_string();
+        }
+
+        let results = board.apply_moves(&moves);
+        turn += 1;
+        for (i, result) in results.iter().enumerate() {
+            if result.is_dead() && eliminated[i].is_none() {
+                eliminated[i] = Some(turn);
+            }
+        }
+        spawn_food(&mut board, rules, &mut rng);
+    }
+    for (i, player) in players.iter_mut().enumerate() {
+        player.end(&request(id, rules, turn, &board, &ids, &latency, i));
+    }
+
+    let mut alive = (0..players.len()).filter(|i| board.snakes[*i].is_alive());
+    let winner = match (alive.next(), alive.next()) {
+        (Some(i), None) if players.len() > 1 => Some(i),
+        _ => None,
+    };
+    Outcome {
+        winner,
+        turns: turn,
+        eliminated,
+        log,
+        board: board.to_string(),
+    }
+}
+
+/// Games of one snake go on while it lives, others until one is left.
+fn is_over(board: &Board) -> bool {
+    let alive = board.snakes.iter().filter(|snake| snake.is_alive()).count();
+    alive == 0 || (alive == 1 && board.snakes.len() > 1)
+}
+
+/// The board at the start of a standard game. Snakes start stacked on one
+/// square, on the spots the engine uses when the board is large enough and
+/// anywhere otherwise. Like the engine, a food goes diagonally next to each,
+/// farther from the center on at least one axis and never in a corner, and
+/// one in the center.
+pub(super) fn setup(rules: &Rules, players: usize, rng: &mut Rng) -> Board {
+    let (width, height) = (rules.width as i8, rules.height as i8);
+    let game = Game::new(width, height);
+    let center = ((width - 1) / 2, (height - 1) / 2);
+    let mut starts = if width >= 7 && height >= 7 && players <= 8 {
+        let (right, top) = (width - 2, height - 2);
+        vec![
+            (1, 1),
+            (center.0, 1),
+            (right, 1),
+            (1, center.1),
+            (right, center.1),
+            (1, top),
+            (center.0, top),
+            (right, top),
+        ]
+    } else {
+        (0..width as usize * height as usize)
+            .map(|i| game.pos(i))
+            .collect()
+    };
+    shuffle(&mut starts, rng);
+    starts.truncate(players);
+    assert!(starts.len() == players, "no room for {} snakes", players);
+
+    // Past the start on an axis, going away from the center.
+    let away = |spot: i8, start: i8, center: i8| {
+        (spot < start && start < center) || (center < start && start < spot)
+    };
+    let corner = |(x, y): Pos| (x == 0 || x == width - 1) && (y == 0 || y == height - 1);
+    let mut food: Vec<Pos> = Vec::new();
+    for start in &starts {
+        let spots: Vec<Pos> = [(-1, -1), (-1, 1), (1, -1), (1, 1)]
+            .iter()
+            .map(|(dx, dy)| (start.0 + dx, start.1 + dy))
+            .filter(|spot| game.index(*spot).is_some() && !starts.contains(spot))
+            .filter(|spot| *spot != center && !food.contains(spot) && !corner(*spot))
+            .filter(|spot| away(spot.0, start.0, center.0) || away(spot.1, start.1, center.1))
+            .collect();
+        if !spots.is_empty() {
+            food.push(spots[rng.below(spots.len())]);
+        }
+    }
+    if !starts.contains(&center) {
+        food.push(center);
+    }
+    let snakes = starts
+        .iter()
+        .map(|start| Snake::new(vec![*start; 3]))
+        .collect();
+    Board::new(game, snakes, food, Vec::new())
+}
+
+/// Tops the food up to the minimum, or spawns one by chance otherwise, on
+/// squares nothing covers.
+fn spawn_food(board: &mut Board, rules: &Rules, rng: &mut Rng) {
+    let food = board.food.iter().count();
+    let spawn = if food < rules.minimum_food as usize {
+        rules.minimum_food as usize - food
+    } else if rng.below(100) < rules.food_spawn_chance as usize {
+        1
+    } else {
+        0
+    };
+    for _ in 0..spawn {
+        let squares = board.game.width as usize * board.game.height as usize;
+        let free: Vec<usize> = (0..squares)
+            .filter(|i| matches!(board.get(board.game.pos(*i)), Square::Empty))
+            .collect();
+        if free.is_empty() {
+            break;
+        }
+        board.food.set(free[rng.below(free.len())]);
+    }
+}
+
+fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+fn to_move(direction: &api::Direction) -> Move {
+    match direction {
+        api::Direction::Up => Move::Up,
+        api::Direction::Down => Move::Down,
+        api::Direction::Left => Move::Left,
+        api::Direction::Right => Move::Right,
+    }
+}
+
+/// The request the engine would send snake `you`, every snake on the board
+/// that is still in the game.
+fn request<'a>(
+    id: &'a str,
+    rules: &Rules,
+    turn: u16,
+    board: &Board,
+    ids: &'a [String],
+    latency: &'a [String],
+    you: usize,
+) -> api::MoveRequest<'a> {
+    let snake = |i: usize| {
+        let snake = &board.snakes[i];
+        let body: Vec<api::Point> = snake
+            .body
+            .iter()
+            .map(|pos| api::Point {
+                x: pos.0 as i16,
+                y: pos.1 as i16,
+            })
+            .collect();
+        api::Snake {
+            id: ids[i].as_str().into(),
+            name: ids[i].as_str().into(),
+            health: snake.health.max(0) as u16,
+            head: body
+                .first()
+                .map_or(api::Point { x: 0, y: 0 }, |head| api::Point {
+                    x: head.x,
+                    y: head.y,
+                }),
+            length: body.len() as u16,
+            body,
+            latency: latency[i].as_str().into(),
+            shout: "".into(),
+            squad: "".into(),
+            customizations: api::Customizations::default(),
+        }
+    };
+    let game = &board.game;
+    api::MoveRequest {
+        game: api::Game {
+            id: id.into(),
+            ruleset: api::Ruleset {
+                name: "standard".into(),
+                version: "v1.0.0".into(),
+                settings: api::RulesetSettings {
+                    food_spawn_chance: rules.food_spawn_chance,
+                    minimum_food: rules.minimum_food,
+                    hazard_damage_per_turn: Some(game.hazard_damage as u16),
+                    ..api::RulesetSettings::default()
+                },
+            },
+            map: "standard".into(),
+            timeout: rules.timeout,
+            source: "custom".into(),
+        },
+        turn,
+        board: api::Board {
+            height: rules.height,
+            width: rules.width,
+            food: board
+                .food
+                .iter()
+                .map(|i| game.pos(i))
+                .map(|pos| api::Point {
+                    x: pos.0 as i16,
+                    y: pos.1 as i16,
+                })
+                .collect(),
+            hazards: Vec::new(),
+            snakes: (0..board.snakes.len())
+                .filter(|i| board.snakes[*i].is_alive())
+                .map(snake)
+                .collect(),
+        },
+        you: snake(you),
+    }
+}
@@ -0,0 +1,197 @@
+use super::bits::BitSet;
+use super::*;
+
+const CONTESTED: u8 = 254;
+const UNCLAIMED: u8 = 255;
+
+/// Turns until each square is vacated by the body covering it, zero for empty
+/// squares. Stacked tails keep the largest.
+fn vacate_times(board: &Board) -> [u16; BitSet::CAPACITY] {
+    let mut free_at = [0u16; BitSet::CAPACITY];
+    for snake in board.snakes.iter() {
+        let len = snake.len();
+        for (i, pos) in snake.body.iter().enumerate() {
+            if let Some(sq) = board.game.index(pos) {
+                free_at[sq] = free_at[sq].max((len - i) as u16);
+            }
+        }
+    }
+    free_at
+}
+
+/// Counts the squares a snake can reach from its head. Body segments are
+/// treated as free from the turn their snake's tail has moved past them, so
+/// following a tail out of a pocket is not considered a trap.
+pub(super) fn flood_fill(board: &Board, player: SnakeID) -> usize {
+    let snake = board.snake(player);
+    if !snake.is_alive() {
+        return 0;
+    }
+    reachable(board, snake.head(), 0)
+}
+
+/// Like `flood_fill`, from any square reached `delay` turns from now.
+pub(super) fn reachable(board: &Board, from: Pos, delay: u16) -> usize {
+    let game = &board.game;
+    let free_at = vacate_times(board);
+
+    let mut seen = BitSet::new();
+    let mut queue = [(0u16, 0u16); BitSet::CAPACITY];
+    let (mut read, mut write) = (0, 0);
+    let start = match game.index(from) {
+        Some(start) => start,
+        None => return 0,
+    };
+    seen.set(start);
+    queue[write] = (start as u16, delay);
+    write += 1;
+
+    let mut count = 0;
+    while read < write {
+        let (sq, dist) = queue[read];
+        read += 1;
+        for m in Move::all() {
+            let next = match game.index(m.next(game.pos(sq as usize))) {
+                Some(next) => next,
+                None => continue,
+            };
+            if seen.has(next) || free_at[next] > dist + 1 {
+                continue;
+            }
+            seen.set(next);
+            queue[write] = (next as u16, dist + 1);
+            write += 1;
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Whether a body still covers the square `turns` turns from now.
+pub(super) fn is_blocked(board: &Board, sq: usize, turns: u16) -> bool {
+    let game = &board.game;
+    board.snakes.iter().any(|snake| {
+        let len = snake.len();
+        snake
+            .body
+            .iter()
+            .enumerate()
+            .any(|(i, pos)| game.index(pos) == Some(sq) && (len - i) as u16 > turns)
+    })
+}
+
+/// Squares each snake reaches before any other, from a breadth first search
+/// out of every head at once. Squares reached on the same turn go to the
+/// longest snake, or to nobody when the longest are tied.
+pub(super) struct Territory {
+    width: i8,
+    height: i8,
+    owner: [u8; BitSet::CAPACITY],
+    pub(super) squares: SmallVec<[usize; 4]>,
+    pub(super) food: SmallVec<[usize; 4]>,
+}
+
+impl Territory {
+    /// Share of all claimed squares owned by the snake.
+    pub(super) fn share(&self, player: SnakeID) -> f32 {
+        let total: usize = self.squares.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        self.squares[player.0] as f32 / total as f32
+    }
+}
+
+pub(super) fn voronoi(board: &Board) -> Territory {
+    let game = &board.game;
+    let free_at = vacate_times(board);
+    let mut territory = Territory {
+        width: game.width,
+        height: game.height,
+        owner: [UNCLAIMED; BitSet::CAPACITY],
+        squares: smallvec![0; board.snakes.len()],
+        food: smallvec![0; board.snakes.len()],
+    };
+    // Length of the snakes claiming a square, to settle ties.
+    let mut claim = [0u16; BitSet::CAPACITY];
+
+    let mut seen = BitSet::new();
+    let mut queue = [0u16; BitSet::CAPACITY];
+    let (mut read, mut write) = (0, 0);
+    for (i, snake) in board.snakes.iter().enumerate() {
+        if let Some(head) = snake.body.first().and_then(|head| game.index(head)) {
+            seen.set(head);
+            territory.owner[head] = i as u8;
+            queue[write] = head as u16;
+            write += 1;
+        }
+    }
+
+    let mut dist = 0;
+    while read < write {
+        let layer = write;
+        dist += 1;
+        let mut reached = BitSet::new();
+        for r in read..layer {
+            let sq = queue[r];
+            let owner = territory.owner[sq as usize];
+            if owner == CONTESTED {
+                continue;
+            }
+            let len = board.snakes[owner as usize].len() as u16;
+            for m in Move::all() {
+                let next = match game.index(m.next(game.pos(sq as usize))) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                if seen.has(next) || free_at[next] > dist {
+                    continue;
+                }
+                if !reached.has(next) {
+                    reached.set(next);
+                    territory.owner[next] = owner;
+                    claim[next] = len;
+                    queue[write] = next as u16;
+                    write += 1;
+                } else if territory.owner[next] != owner && len >= claim[next] {
+                    territory.owner[next] = if len > claim[next] { owner } else { CONTESTED };
+                    claim[next] = len;
+                }
+            }
+        }
+        for &sq in &queue[layer..write] {
+            seen.set(sq as usize);
+        }
+        read = layer;
+    }
+
+    for &sq in &queue[..write] {
+        let owner = territory.owner[sq as usize] as usize;
+        if owner >= board.snakes.len() {
+            continue;
+        }
+        territory.squares[owner] += 1;
+        if board.food.has(sq as usize) {
+            territory.food[owner] += 1;
+        }
+    }
+    territory
+}
+
+impl fmt::Display for Territory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..self.height {
+            let y = self.height - row - 1;
+            write!(f, "  ")?;
+            for x in 0..self.width {
+                match self.owner[y as usize * self.width as usize + x as usize] {
+                    CONTESTED => write!(f, "- ")?,
+                    UNCLAIMED => write!(f, "_ ")?,
+                    owner => write!(f, "{} ", owner)?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
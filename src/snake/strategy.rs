@@ -0,0 +1,72 @@
+use super::greedy::Greedy;
+use super::mcts::{Mcts, Playout};
+use super::minmax::Minimax;
+use super::*;
+
+/// What a strategy knows about the game besides the board.
+pub(super) struct Meta<'a> {
+    pub(super) game: &'a str,
+    pub(super) ruleset: &'a str,
+    pub(super) turn: u16,
+    /// Time the strategy may spend picking a move.
+    pub(super) sla: Duration,
+    /// Threads the strategy may search on.
+    pub(super) threads: usize,
+    /// Moves from the root down to which the search tree is returned, if
+    /// it should be.
+    pub(super) tree_depth: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub(super) depth: usize,
+    pub(super) nodes: usize,
+    pub(super) score: f32,
+    pub(super) elapsed: Duration,
+    /// Whether the search ran out of time before its depth limit.
+    pub(super) cut_short: bool,
+    /// The search tree when asked for and the strategy builds one.
+    pub(super) tree: Option<String>,
+}
+
+impl Diagnostics {
+    pub fn tree(&self) -> Option<&str> {
+        self.tree.as_deref()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth: {}, nodes: {}, score: {}, elapsed: {:?}, cut short: {}",
+            self.depth, self.nodes, self.score, self.elapsed, self.cut_short
+        )
+    }
+}
+
+pub(super) struct Decision {
+    pub(super) moved: Move,
+    pub(super) diagnostics: Diagnostics,
+}
+
+/// An algorithm picking our move. Snake 0 on the board is always us.
+pub(super) trait Strategy: Send {
+    fn name(&self) -> &'static str;
+
+    fn decide(&mut self, board: &Board, meta: &Meta) -> Decision;
+}
+
+/// Names accepted by `by_name`.
+pub(super) const STRATEGIES: &[&str] = &["minimax", "greedy", "mcts", "mcts-random"];
+
+pub(super) fn by_name(name: &str, settings: &Settings) -> Option<Box<dyn Strategy>> {
+    let playout_depth = settings.playout_depth;
+    match name {
+        "minimax" => Some(Box::new(Minimax::new(settings))),
+        "greedy" => Some(Box::new(Greedy)),
+        "mcts" => Some(Box::new(Mcts::new(Playout::Heuristic, playout_depth, 0x5eed))),
+        "mcts-random" => Some(Box::new(Mcts::new(Playout::Random, playout_depth, 0x5eed))),
+        _ => None,
+    }
+}
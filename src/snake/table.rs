@@ -0,0 +1,64 @@
+use super::*;
+
+/// What a stored score says about the true value of the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Bound {
+    Exact,
+    /// The search failed high, the value is at least the score.
+    Lower,
+    /// The search failed low, the value is at most the score.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Entry {
+    key: u64,
+    /// Plies searched below the position.
+    pub(super) depth: u16,
+    pub(super) bound: Bound,
+    pub(super) score: f32,
+    /// Our best move from the position, tried first when it comes up again.
+    pub(super) best: Move,
+}
+
+/// Fixed size transposition table indexed by the low bits of the Zobrist
+/// key. A slot holding the same position is only overwritten by a search at
+/// least as deep, any other position replaces it.
+pub(super) struct Table {
+    entries: Vec<Option<Entry>>,
+    mask: usize,
+}
+
+impl Table {
+    pub(super) fn new(bits: u32) -> Table {
+        Table {
+            entries: vec![None; 1 << bits],
+            mask: (1 << bits) - 1,
+        }
+    }
+
+    pub(super) fn get(&self, key: u64) -> Option<&Entry> {
+        self.entries[key as usize & self.mask]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+    }
+
+    pub(super) fn put(&mut self, key: u64, depth: u16, bound: Bound, score: f32, best: Move) {
+        let slot = &mut self.entries[key as usize & self.mask];
+        if matches!(slot, Some(entry) if entry.key == key && entry.depth > depth) {
+            return;
+        }
+        *slot = Some(Entry {
+            key,
+            depth,
+            bound,
+            score,
+            best,
+        });
+    }
+
+    #[cfg(test)]
+    pub(super) fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+}
@@ -1,5 +1,6 @@
 extern crate test;
 
+use super::super::{alloc, api};
 use super::minmax::Walker;
 use super::*;
 use std::fs::File;
@@ -8,9 +9,10 @@ use test::Bencher;
 
 struct Scenario {
     name: &'static str,
-    width: isize,
-    height: isize,
+    width: i8,
+    height: i8,
     food: Vec<Pos>,
+    hazards: Vec<Pos>,
     snakes: Vec<Snake>,
     depth: usize,
 }
@@ -18,23 +20,25 @@ struct Scenario {
 impl Scenario {
     fn snap(&self, node: &Node) {
         let mut f = File::create("src/snake/testdata/".to_string() + self.name).unwrap();
-        write!(f, "board:\n").unwrap();
-        write!(f, "{}\n", node.board).unwrap();
-        write!(f, "tree:\n").unwrap();
+        writeln!(f, "board:").unwrap();
+        writeln!(f, "{}", node.board).unwrap();
+        writeln!(f, "tree:").unwrap();
         write!(f, "{}", node).unwrap();
 
-        print!("board:\n");
-        print!("{}\n", node.board);
-        print!("tree:\n");
+        println!("board:");
+        println!("{}", node.board);
+        println!("tree:");
         print!("{}", node);
     }
 
     fn prepare(&self) -> (Board, Options) {
-        let game = Game {
-            width: self.width,
-            height: self.height,
-        };
-        let board = Board::new(game, self.snakes.clone(), self.food.clone());
+        let game = Game::new(self.width, self.height);
+        let board = Board::new(
+            game,
+            self.snakes.clone(),
+            self.food.clone(),
+            self.hazards.clone(),
+        );
         let opts = Options {
             max_depth: self.depth,
             sla: Duration::from_secs(20),
@@ -43,10 +47,11 @@ impl Scenario {
     }
 }
 
-fn run_test(s: Scenario) {
+fn run_test(s: Scenario) -> Node {
     let (board, opts) = s.prepare();
     let node = Walker::new().walk(board, opts);
     s.snap(&node);
+    node
 }
 
 fn run_bench(b: &mut Bencher, s: Scenario) {
@@ -63,9 +68,10 @@ fn basic_single_player_setup() {
         width: 5,
         height: 5,
         food: vec![(0, 0)],
+        hazards: vec![],
         snakes: vec![Snake::new(vec![(0, 1), (0, 2)])],
         depth: 2,
-    })
+    });
 }
 
 #[test]
@@ -75,12 +81,13 @@ fn basic_multi_player_setup() {
         width: 5,
         height: 5,
         food: vec![(0, 0)],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(0, 1), (0, 2)]),
             Snake::new(vec![(2, 2), (2, 3)]),
         ],
         depth: 2,
-    })
+    });
 }
 
 #[test]
@@ -90,6 +97,7 @@ fn basic_large_player_setup() {
         height: 10,
         width: 10,
         food: vec![(0, 0)],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(0, 1), (0, 2)]),
             Snake::new(vec![(2, 2), (2, 3)]),
@@ -107,6 +115,7 @@ fn potential_death() {
         height: 5,
         width: 5,
         food: vec![],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(1, 0)]),
             Snake::new(vec![(0, 1), (0, 2), (0, 3)]),
@@ -117,14 +126,27 @@ fn potential_death() {
 
 #[test]
 fn eats_food() {
-    run_test(Scenario {
+    let s = Scenario {
         name: "eats_food",
         height: 5,
         width: 5,
         food: vec![(0, 0)],
+        hazards: vec![],
         snakes: vec![Snake::new(vec![(1, 0)])],
         depth: 2,
-    });
+    };
+    let (mut board, opts) = s.prepare();
+
+    // Growing onto its own head does not kill a snake of one.
+    let results = board.apply_moves(&[Move::Left]);
+    assert!(matches!(results[0], Result::Eat));
+    assert!(board.snake(SnakeID(0)).is_alive());
+    assert_eq!(board.snake(SnakeID(0)).len(), 2);
+    assert!(!board.food.has(board.game.index((0, 0)).unwrap()));
+
+    // And the search goes on from there.
+    let node = Walker::new().walk(board, opts);
+    assert!(!node.edges.is_empty(), "{}", node);
 }
 
 #[test]
@@ -134,6 +156,7 @@ fn trapped_myself() {
         width: 11,
         height: 11,
         food: vec![],
+        hazards: vec![],
         snakes: vec![Snake::new(vec![
             (0, 8),
             (1, 8),
@@ -153,6 +176,7 @@ fn trapped_by_another() {
         width: 5,
         height: 5,
         food: vec![],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(2, 0), (2, 1)]),
             Snake::new(vec![(1, 1), (0, 1), (0, 2), (0, 3)]),
@@ -168,6 +192,7 @@ fn trapped_game_regression_1() {
         width: 11,
         height: 11,
         food: vec![(6, 6)],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
             Snake::new(vec![
@@ -185,6 +210,730 @@ fn trapped_game_regression_1() {
     });
 }
 
+#[test]
+fn avoids_hazard() {
+    let node = run_test(Scenario {
+        name: "avoids_hazard",
+        width: 5,
+        height: 5,
+        food: vec![],
+        hazards: vec![(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (1, 3), (2, 3), (3, 3), (4, 3)],
+        snakes: vec![Snake::new(vec![(1, 2), (2, 2), (3, 2)])],
+        depth: 2,
+    });
+    assert!(matches!(node.pick(), Move::Down));
+}
+
+#[test]
+fn eats_before_starving() {
+    let node = run_test(Scenario {
+        name: "eats_before_starving",
+        width: 5,
+        height: 5,
+        food: vec![(4, 0)],
+        hazards: vec![],
+        snakes: vec![Snake::new(vec![(2, 0), (1, 0), (0, 0)]).with_health(2)],
+        depth: 3,
+    });
+    assert!(matches!(node.pick(), Move::Right));
+}
+
+#[test]
+fn avoids_dead_end_pocket() {
+    let node = run_test(Scenario {
+        name: "avoids_dead_end_pocket",
+        width: 7,
+        height: 7,
+        food: vec![],
+        hazards: vec![],
+        snakes: vec![Snake::new(vec![
+            (4, 2),
+            (4, 3),
+            (4, 4),
+            (3, 4),
+            (2, 4),
+            (1, 4),
+            (1, 3),
+            (1, 2),
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (4, 1),
+            (5, 1),
+            (6, 1),
+            (6, 0),
+        ])],
+        depth: 1,
+    });
+    assert!(matches!(node.pick(), Move::Right));
+}
+
+#[test]
+fn strategies_avoid_dead_end_pocket() {
+    let board = Board::new(
+        Game::new(7, 7),
+        vec![Snake::new(vec![
+            (4, 2),
+            (4, 3),
+            (4, 4),
+            (3, 4),
+            (2, 4),
+            (1, 4),
+            (1, 3),
+            (1, 2),
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (4, 1),
+            (5, 1),
+            (6, 1),
+            (6, 0),
+        ])],
+        vec![],
+        vec![],
+    );
+    let meta = strategy::Meta {
+        game: "test",
+        ruleset: "solo",
+        turn: 0,
+        sla: Duration::from_millis(20),
+        threads: 1,
+        tree_depth: None,
+    };
+    for name in strategy::STRATEGIES {
+        let decision = strategy::by_name(name, &Settings::default()).unwrap().decide(&board, &meta);
+        assert!(matches!(decision.moved, Move::Right), "{}", name);
+    }
+}
+
+#[test]
+fn mcts_survives_where_minimax_does() {
+    let scenarios = vec![
+        Scenario {
+            name: "potential_death",
+            height: 5,
+            width: 5,
+            food: vec![],
+            hazards: vec![],
+            snakes: vec![
+                Snake::new(vec![(1, 0)]),
+                Snake::new(vec![(0, 1), (0, 2), (0, 3)]),
+            ],
+            depth: 2,
+        },
+        Scenario {
+            name: "trapped_myself",
+            width: 11,
+            height: 11,
+            food: vec![],
+            hazards: vec![],
+            snakes: vec![Snake::new(vec![
+                (0, 8),
+                (1, 8),
+                (1, 9),
+                (1, 10),
+                (2, 10),
+                (3, 10),
+            ])],
+            depth: 3,
+        },
+        Scenario {
+            name: "trapped_by_another",
+            width: 5,
+            height: 5,
+            food: vec![],
+            hazards: vec![],
+            snakes: vec![
+                Snake::new(vec![(2, 0), (2, 1)]),
+                Snake::new(vec![(1, 1), (0, 1), (0, 2), (0, 3)]),
+            ],
+            depth: 6,
+        },
+    ];
+    let meta = strategy::Meta {
+        game: "test",
+        ruleset: "standard",
+        turn: 0,
+        sla: Duration::from_millis(50),
+        threads: 1,
+        tree_depth: None,
+    };
+    for s in scenarios {
+        let (board, opts) = s.prepare();
+        let node = Walker::new().walk(board.clone(), opts);
+        let best = node
+            .edges
+            .iter()
+            .map(|edge| edge.score)
+            .fold(f32::INFINITY, f32::min);
+        if best == f32::INFINITY {
+            continue;
+        }
+        for name in &["mcts", "mcts-random"] {
+            let decision = strategy::by_name(name, &Settings::default()).unwrap().decide(&board, &meta);
+            let edge = node
+                .edges
+                .iter()
+                .find(|edge| edge.moved == decision.moved)
+                .unwrap();
+            assert!(
+                edge.score < f32::INFINITY,
+                "{} {} picked {:?}, minimax picked {:?}",
+                s.name,
+                name,
+                decision.moved,
+                node.pick()
+            );
+        }
+    }
+}
+
+#[test]
+fn flood_fill_follows_tails() {
+    let board = Board::new(
+        Game::new(5, 5),
+        vec![
+            Snake::new(vec![(0, 0), (1, 0), (1, 1), (0, 1)]),
+            Snake::new(vec![(3, 3), (3, 4)]),
+        ],
+        vec![],
+        vec![],
+    );
+    // The corner is closed off by our own body, but the tail moves out of
+    // the way in time and every other square opens up behind it.
+    assert_eq!(space::flood_fill(&board, SnakeID(0)), 24);
+
+    let board = Board::new(
+        Game::new(5, 5),
+        vec![
+            Snake::new(vec![(0, 0)]),
+            Snake::new(vec![(2, 0), (1, 0), (1, 1), (0, 1), (0, 2)]),
+        ],
+        vec![],
+        vec![],
+    );
+    assert_eq!(space::flood_fill(&board, SnakeID(0)), 0);
+}
+
+#[test]
+fn voronoi_splits_territory() {
+    let board = Board::new(
+        Game::new(5, 5),
+        vec![
+            Snake::new(vec![(0, 2), (0, 1), (0, 0)]),
+            Snake::new(vec![(4, 2), (4, 3)]),
+        ],
+        vec![(1, 4), (3, 0)],
+        vec![],
+    );
+    let territory = space::voronoi(&board);
+    // The middle column is as close to both heads and goes to the longer
+    // snake, bodies are claimed by whoever reaches them once vacated.
+    assert_eq!(territory.squares.as_slice(), &[15, 10]);
+    assert_eq!(territory.food.as_slice(), &[1, 1]);
+    assert!((territory.share(SnakeID(0)) - 0.6).abs() < 1e-6);
+}
+
+#[test]
+fn apply_moves_head_to_head() {
+    let mut board = Board::new(
+        Game::new(5, 5),
+        vec![
+            Snake::new(vec![(1, 2), (0, 2), (0, 1)]),
+            Snake::new(vec![(3, 2), (4, 2)]),
+        ],
+        vec![(2, 2)],
+        vec![],
+    );
+    let results = board.apply_moves(&[Move::Right, Move::Left]);
+    assert!(matches!(results[0], Result::Kill));
+    assert!(matches!(results[1], Result::Killed));
+    assert!(!board.snake(SnakeID(1)).is_alive());
+    assert_eq!(board.snake(SnakeID(0)).len(), 4);
+    assert_eq!(board.snake(SnakeID(0)).health, MAX_HEALTH);
+    assert!(matches!(board.get((2, 2)), Square::Snake(SnakeID(0))));
+}
+
+#[test]
+fn apply_moves_chases_tails() {
+    let mut board = Board::new(
+        Game::new(5, 5),
+        vec![
+            Snake::new(vec![(1, 1), (1, 2), (2, 2), (2, 1)]),
+            Snake::new(vec![(3, 1), (4, 1), (4, 0)]),
+        ],
+        vec![],
+        vec![],
+    );
+    let results = board.apply_moves(&[Move::Right, Move::Down]);
+    assert!(matches!(results[0], Result::None));
+    assert!(matches!(results[1], Result::None));
+    assert_eq!(board.snake(SnakeID(0)).head(), (2, 1));
+    assert_eq!(board.snake(SnakeID(0)).health, MAX_HEALTH - 1);
+}
+
+#[test]
+fn body_ring_buffer() {
+    let mut body = bits::Body::new(vec![(2, 2), (2, 1), (2, 0)]);
+    body.advance((3, 2));
+    body.grow();
+    body.advance((3, 3));
+    body.advance((4, 3));
+    body.grow();
+    body.grow();
+    assert_eq!(
+        body.iter().collect::<Vec<Pos>>(),
+        vec![(4, 3), (3, 3), (3, 2), (2, 2), (2, 2), (2, 2)]
+    );
+    assert_eq!(body.advance((4, 4)), (2, 2));
+    assert_eq!(body.tail(), (2, 2));
+}
+
+#[test]
+fn zobrist_hash_follows_positions() {
+    let board = Board::new(
+        Game::new(7, 7),
+        vec![
+            Snake::new(vec![(1, 1), (1, 0)]),
+            Snake::new(vec![(5, 5), (5, 6)]),
+        ],
+        vec![(3, 3)],
+        vec![],
+    );
+    // Up then right or right then up, the same position once the neck
+    // follows.
+    let mut a = board.clone();
+    for m in [Move::Up, Move::Right, Move::Right] {
+        a.apply_moves(&[m, Move::Left]);
+    }
+    let mut b = board.clone();
+    for m in [Move::Right, Move::Up, Move::Right] {
+        b.apply_moves(&[m, Move::Left]);
+    }
+    assert_eq!(zobrist::hash(&a), zobrist::hash(&b));
+    assert_ne!(zobrist::hash(&a), zobrist::hash(&board));
+
+    let mut hungrier = a.clone();
+    hungrier.snakes[0].health -= 1;
+    assert_ne!(zobrist::hash(&a), zobrist::hash(&hungrier));
+    let mut swapped = a.clone();
+    swapped.snakes.swap(0, 1);
+    assert_ne!(zobrist::hash(&a), zobrist::hash(&swapped));
+}
+
+#[test]
+fn transposition_table_keeps_results() {
+    let s = Scenario {
+        name: "transposition_table_keeps_results",
+        width: 11,
+        height: 11,
+        food: vec![(6, 6)],
+        hazards: vec![],
+        snakes: vec![
+            Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
+            Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4)]),
+        ],
+        depth: 6,
+    };
+    let (board, opts) = s.prepare();
+    let mut walker = Walker::new();
+    let first = walker.walk(board.clone(), opts);
+    assert!(walker.hits() > 0);
+
+    // A second walk of the same position starts from the stored results.
+    let nodes = walker.nodes();
+    let (_, opts) = s.prepare();
+    let second = walker.walk(board, opts);
+    assert!(walker.nodes() < nodes);
+    assert_eq!(first.pick(), second.pick());
+}
+
+#[test]
+fn parallel_search_agrees() {
+    for (name, snakes, depth) in [
+        (
+            "two_snakes",
+            vec![
+                Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
+                Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4), (5, 3), (6, 3), (7, 3), (8, 3)]),
+            ],
+            4,
+        ),
+        (
+            "trapped_myself",
+            vec![Snake::new(vec![(0, 8), (1, 8), (1, 9), (1, 10), (2, 10), (3, 10)])],
+            4,
+        ),
+    ] {
+        let s = Scenario {
+            name,
+            width: 11,
+            height: 11,
+            food: vec![(6, 6)],
+            hazards: vec![],
+            snakes,
+            depth,
+        };
+        let (board, opts) = s.prepare();
+        let single = Walker::new().walk(board.clone(), opts);
+        let mut walker = Walker::new();
+        walker.set_threads(4);
+        let parallel = walker.walk(board, opts);
+
+        let best = |node: &Node| {
+            node.edges
+                .iter()
+                .map(|edge| edge.score)
+                .fold(f32::INFINITY, f32::min)
+        };
+        assert_eq!(single.pick(), parallel.pick(), "{}", name);
+        assert_eq!(best(&single), best(&parallel), "{}", name);
+        assert_eq!(parallel.edges.len(), 4, "{}", name);
+    }
+}
+
+#[test]
+fn iterative_deepening_stops_at_sla() {
+    let s = Scenario {
+        name: "iterative_deepening_stops_at_sla",
+        width: 11,
+        height: 11,
+        food: vec![(6, 6)],
+        hazards: vec![],
+        snakes: vec![
+            Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
+            Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4)]),
+        ],
+        depth: 200,
+    };
+    let (board, mut opts) = s.prepare();
+    opts.sla = Duration::from_millis(20);
+    let mut walker = Walker::new();
+    let node = walker.walk(board.clone(), opts);
+    assert!(walker.depth() >= 1 && walker.depth() < 100);
+    assert!(walker.cut_short());
+    assert!(!node.edges.is_empty());
+
+    opts.max_depth = 2;
+    opts.sla = Duration::from_secs(20);
+    walker.walk(board, opts);
+    assert_eq!(walker.depth(), 1);
+    assert!(!walker.cut_short());
+}
+
+#[test]
+fn walker_reuses_its_nodes() {
+    let s = Scenario {
+        name: "walker_reuses_its_nodes",
+        width: 11,
+        height: 11,
+        food: vec![(6, 6)],
+        hazards: vec![],
+        snakes: vec![
+            Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
+            Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4)]),
+        ],
+        depth: 6,
+    };
+    let (board, opts) = s.prepare();
+    let mut walker = Walker::new();
+    let mut walk = |board: Board| {
+        walker.clear_table();
+        let allocations = alloc::Scope::start();
+        let node = walker.walk(board, opts);
+        let moved = node.pick();
+        walker.recycle(node);
+        (moved, allocations.usage().calls)
+    };
+    let (first, calls) = walk(board.clone());
+    assert!(calls > 0);
+
+    // The same search again only fills in the nodes of the last one.
+    let (second, calls) = walk(board.clone());
+    assert_eq!(first, second);
+    assert_eq!(calls, 0);
+
+    // Helpers take their nodes from the spares too, once the first walk on
+    // threads grew them it is only starting the threads that allocates.
+    let mut opts = opts;
+    opts.max_depth = 8;
+    let mut walker = Walker::new();
+    walker.set_threads(4);
+    let mut walk = || {
+        walker.clear_table();
+        let allocations = alloc::Scope::start();
+        let node = walker.walk(board.clone(), opts);
+        walker.recycle(node);
+        allocations.usage().calls
+    };
+    assert!(walk() > 1000);
+    // Trees differ a little from walk to walk, the helpers race for moves.
+    let calls = (0..4).map(|_| walk()).min().unwrap();
+    assert!(calls < 100, "{} allocations", calls);
+
+    // Bodies too long to be stored inline keep their buffer once grown.
+    let long: Vec<Pos> = (0..11).flat_map(|y| (0..3).map(move |x| (x, y))).collect();
+    let source = Body::new(long.clone());
+    let mut copy = Body::new(long);
+    copy.clear();
+    copy.clone_from(&source);
+    let allocations = alloc::Scope::start();
+    copy.clone_from(&source);
+    assert_eq!(allocations.usage().calls, 0);
+    assert!(copy.iter().eq(source.iter()));
+}
+
+#[test]
+fn clock_keeps_latency_back() {
+    let mut clock = clock::Clock::new(Duration::from_millis(50));
+    let deadline = Duration::from_millis(500);
+    // Nothing measured yet, half the timeout is kept for the network.
+    assert_eq!(
+        clock.budget(500, deadline, clock::Position::Critical),
+        Duration::from_millis(200)
+    );
+
+    clock.observe(None);
+    clock.spent(Duration::from_millis(100));
+    clock.observe(Some(180));
+    assert_eq!(
+        clock.budget(500, deadline, clock::Position::Critical),
+        Duration::from_millis(370)
+    );
+    assert!(
+        clock.budget(500, deadline, clock::Position::Trivial)
+            < clock.budget(500, deadline, clock::Position::Normal)
+    );
+
+    // The worst recent overhead counts.
+    clock.spent(Duration::from_millis(100));
+    clock.observe(Some(400));
+    clock.spent(Duration::from_millis(100));
+    clock.observe(Some(110));
+    assert_eq!(
+        clock.budget(500, deadline, clock::Position::Critical),
+        Duration::from_millis(150)
+    );
+    assert!(clock.budget(100, deadline, clock::Position::Critical) > Duration::from_millis(0));
+}
+
+#[test]
+fn clock_ends_searches_before_the_deadline() {
+    let mut clock = clock::Clock::new(Duration::from_millis(50));
+    let deadline = Runner::new().deadline(500);
+    assert_eq!(deadline, Duration::from_millis(400));
+
+    // Little overhead leaves more than the server waits for.
+    clock.spent(Duration::from_millis(300));
+    clock.observe(Some(320));
+    let budget = clock.budget(500, deadline, clock::Position::Critical);
+    assert_eq!(budget, Duration::from_millis(350));
+    assert!(clock.budget(500, deadline, clock::Position::Normal) < budget);
+}
+
+#[test]
+fn sessions_come_back_from_moves() {
+    let sessions = session::Sessions::default();
+    let new = || session::Session::new(Duration::from_millis(50));
+    sessions.insert("game", new());
+
+    // A move that panics still puts the session back.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut session = sessions.lend("game", new);
+        session.turn = 3;
+        panic!("search failed");
+    }));
+    assert!(result.is_err());
+
+    // A move given up on comes back after the next one and is dropped.
+    let mut late = sessions.lend("game", new);
+    late.turn = 4;
+    let mut next = sessions.lend("game", new);
+    next.turn = 5;
+    drop(next);
+    drop(late);
+    assert_eq!(sessions.take("game").map(|session| session.turn), Some(5));
+}
+
+#[test]
+fn clock_classifies_positions() {
+    let game = Game::new(11, 11);
+    let open = Board::new(game.clone(), vec![Snake::new(vec![(5, 5), (5, 4)])], vec![], vec![]);
+    assert_eq!(clock::Position::of(&open), clock::Position::Normal);
+
+    let corridor = Board::new(
+        game.clone(),
+        vec![Snake::new(vec![(0, 0), (1, 0), (1, 1), (1, 2), (0, 2)])],
+        vec![],
+        vec![],
+    );
+    assert_eq!(clock::Position::of(&corridor), clock::Position::Trivial);
+
+    let close = Board::new(
+        game,
+        vec![
+            Snake::new(vec![(5, 5), (5, 4)]),
+            Snake::new(vec![(6, 6), (6, 7)]),
+        ],
+        vec![],
+        vec![],
+    );
+    assert_eq!(clock::Position::of(&close), clock::Position::Critical);
+}
+
+#[test]
+fn fallback_avoids_walls_bodies_and_heads() {
+    let raw = |hazards: &str| {
+        format!(
+            r#"{{"game":{{"id":"g","ruleset":{{"name":"standard","version":"v1"}},"timeout":500}},
+            "turn":3,"board":{{"width":11,"height":11,"food":[],"hazards":[{}],"snakes":[
+            {{"id":"me","health":90,"body":[{{"x":0,"y":1}},{{"x":0,"y":2}},{{"x":0,"y":3}}],"head":{{"x":0,"y":1}},"length":3}},
+            {{"id":"other","health":90,"body":[{{"x":2,"y":1}},{{"x":3,"y":1}},{{"x":4,"y":1}},{{"x":5,"y":1}}],"head":{{"x":2,"y":1}},"length":4}}]}},
+            "you":{{"id":"me","health":90,"body":[{{"x":0,"y":1}},{{"x":0,"y":2}},{{"x":0,"y":3}}],"head":{{"x":0,"y":1}},"length":3}}}}"#,
+            hazards
+        )
+    };
+    // Up is the body, left the wall and right next to a longer head.
+    let json = raw("");
+    let req: api::MoveRequest = serde_json::from_str(&json).unwrap();
+    assert_eq!(fallback(&req), api::Direction::Down);
+    // A hazard is still better than a head to head.
+    let json = raw(r#"{"x":0,"y":0}"#);
+    let req: api::MoveRequest = serde_json::from_str(&json).unwrap();
+    assert_eq!(fallback(&req), api::Direction::Down);
+}
+
+#[test]
+fn falls_back_on_boards_too_large() {
+    let json = r#"{"game":{"id":"g","ruleset":{"name":"standard","version":"v1"},"timeout":500},
+        "turn":3,"board":{"width":30,"height":30,"food":[],"snakes":[
+        {"id":"me","health":90,"body":[{"x":29,"y":1},{"x":29,"y":2}],"head":{"x":29,"y":1},"length":2}]},
+        "you":{"id":"me","health":90,"body":[{"x":29,"y":1},{"x":29,"y":2}],"head":{"x":29,"y":1},"length":2}}"#;
+    let req: api::MoveRequest = serde_json::from_str(json).unwrap();
+    let mut runner = Runner::new();
+    let (moved, diagnostics) = runner.run(&req);
+    assert_eq!(moved, fallback(&req));
+    assert_eq!(diagnostics.nodes, 0);
+    assert_eq!(runner.board(&req), "unsupported board size 30x30\n");
+}
+
+#[test]
+fn reads_the_rules_of_requests() {
+    let json = |settings: &str| {
+        format!(
+            r#"{{"game":{{"id":"g","ruleset":{{"name":"standard","version":"v1"{}}},"timeout":500}},
+            "turn":3,"board":{{"width":5,"height":5,"food":[],"snakes":[
+            {{"id":"me","health":90,"body":[{{"x":2,"y":1}},{{"x":2,"y":2}}],"head":{{"x":2,"y":1}},"length":2}}]}},
+            "you":{{"id":"me","health":90,"body":[{{"x":2,"y":1}},{{"x":2,"y":2}}],"head":{{"x":2,"y":1}},"length":2}}}}"#,
+            settings
+        )
+    };
+    let game = |runner: &Runner, settings: &str| {
+        let json = json(settings);
+        let req: api::MoveRequest = serde_json::from_str(&json).unwrap();
+        runner.read_board(&req).unwrap().game
+    };
+    let mut runner = Runner::new();
+    let standard = game(&runner, "");
+    assert_eq!(
+        (standard.hazard_damage, standard.max_health),
+        (HAZARD_DAMAGE, MAX_HEALTH)
+    );
+    let harmless = game(&runner, r#","settings":{"hazardDamagePerTurn":0}"#);
+    assert_eq!(harmless.hazard_damage, 0);
+
+    runner.set_settings(Settings {
+        max_health: 150,
+        ..Settings::default()
+    });
+    assert_eq!(game(&runner, "").max_health, 150);
+}
+
+/// Goes up whatever happens.
+struct Stubborn;
+
+impl sim::Player for Stubborn {
+    fn start(&mut self, _: &api::MoveRequest) {}
+
+    fn play(&mut self, _: &api::MoveRequest) -> std::result::Result<api::Direction, String> {
+        Ok(api::Direction::Up)
+    }
+
+    fn end(&mut self, _: &api::MoveRequest) {}
+}
+
+#[test]
+fn setup_places_food_like_the_engine() {
+    let rules = sim::Rules::default();
+    let center = (5, 5);
+    for seed in 0..50 {
+        for players in 1..=8 {
+            let board = sim::setup(&rules, players, &mut rng::Rng::new(seed));
+            let heads: Vec<Pos> = board.snakes.iter().map(|snake| snake.head()).collect();
+            let food: Vec<Pos> = (0..121)
+                .filter(|i| board.food.has(*i))
+                .map(|i| board.game.pos(i))
+                .collect();
+            assert_eq!(food.len(), players + 1);
+            assert!(food.contains(&center));
+            for spot in food.iter().filter(|spot| **spot != center) {
+                assert!(!matches!(spot, (0, 0) | (0, 10) | (10, 0) | (10, 10)));
+                // Diagonally next to a snake, not towards the center on
+                // both axes.
+                assert!(heads.iter().any(|head| {
+                    let (dx, dy) = (spot.0 - head.0, spot.1 - head.1);
+                    dx.abs() == 1
+                        && dy.abs() == 1
+                        && (dx * (head.0 - center.0) > 0 || dy * (head.1 - center.1) > 0)
+                }));
+            }
+        }
+    }
+}
+
+#[test]
+fn simulates_games() {
+    let rules = sim::Rules::default();
+    let greedy = || -> Box<dyn sim::Player> {
+        let mut runner = Runner::new();
+        runner.set_strategy(None, "greedy").unwrap();
+        runner.set_threads(1);
+        Box::new(runner)
+    };
+    let first = sim::play("sim", &rules, 7, &mut [greedy(), greedy()]);
+    assert!(first.turns > 0);
+    for line in &first.log {
+        let req: api::MoveRequest = serde_json::from_str(line).unwrap();
+        assert_eq!(req.game.id, "sim");
+        assert_eq!(req.game.timeout, 500);
+    }
+    // Greedy picks the same moves on the same board, the seed draws the rest.
+    let second = sim::play("sim", &rules, 7, &mut [greedy(), greedy()]);
+    assert_eq!((&first.board, first.turns), (&second.board, second.turns));
+    assert_eq!(first.winner, second.winner);
+    assert_eq!(first.eliminated, second.eliminated);
+
+    // Alone on the board a snake going up hits the wall, no one wins.
+    let solo = sim::play("solo", &rules, 7, &mut [Box::new(Stubborn)]);
+    assert_eq!(solo.winner, None);
+    assert_eq!(solo.eliminated, vec![Some(solo.turns)]);
+    assert!(solo.turns <= rules.height);
+    assert_eq!(solo.log.len(), solo.turns as usize);
+
+    // Games still going at the last turn are draws.
+    let rules = sim::Rules {
+        max_turns: 3,
+        ..sim::Rules::default()
+    };
+    let capped = sim::play("capped", &rules, 7, &mut [greedy(), greedy()]);
+    assert_eq!((capped.turns, capped.winner), (3, None));
+    assert_eq!(capped.eliminated, vec![None, None]);
+
+    assert!(sim::Remote::new("https://example.com").is_err());
+    assert!(sim::Remote::new("http://").is_err());
+    assert!(sim::Remote::new("http://127.0.0.1:3000/snake/").is_ok());
+}
+
 #[bench]
 fn bench_trapped(b: &mut Bencher) {
     run_bench(b, Scenario {
@@ -192,6 +941,7 @@ fn bench_trapped(b: &mut Bencher) {
         width: 11,
         height: 11,
         food: vec![(6, 6)],
+        hazards: vec![],
         snakes: vec![
             Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
             Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4), (5, 3), (6, 3), (7, 3), (8, 3)]),
@@ -207,7 +957,45 @@ fn bench_basic(b: &mut Bencher) {
         width: 5,
         height: 5,
         food: vec![(0, 0)],
+        hazards: vec![],
         snakes: vec![Snake::new(vec![(0, 1), (0, 2)])],
         depth: 3,
     });
 }
+
+/// Reports a thousand bytes per node searched, so the throughput column reads
+/// as thousands of nodes per second.
+fn run_threads_bench(b: &mut Bencher, threads: usize) {
+    let s = Scenario {
+        name: "threads",
+        width: 11,
+        height: 11,
+        food: vec![(6, 6), (2, 9)],
+        hazards: vec![],
+        snakes: vec![
+            Snake::new(vec![(7, 6), (7, 5), (7, 4)]),
+            Snake::new(vec![(5, 7), (5, 6), (5, 5), (5, 4)]),
+        ],
+        depth: 6,
+    };
+    let mut nodes = 0;
+    b.iter(|| {
+        let (board, opts) = s.prepare();
+        let mut walker = Walker::new();
+        walker.set_threads(threads);
+        let node = walker.walk(board, opts);
+        nodes = walker.nodes();
+        node
+    });
+    b.bytes = nodes as u64 * 1000;
+}
+
+#[bench]
+fn bench_search_1_thread(b: &mut Bencher) {
+    run_threads_bench(b, 1);
+}
+
+#[bench]
+fn bench_search_4_threads(b: &mut Bencher) {
+    run_threads_bench(b, 4);
+}
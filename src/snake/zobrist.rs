@@ -0,0 +1,68 @@
+use super::bits::BitSet;
+use super::rng::Rng;
+use super::*;
+use lazy_static::lazy_static;
+
+/// Snakes with keys of their own, any further snakes share them.
+const SNAKES: usize = 8;
+const SQUARES: usize = BitSet::CAPACITY;
+/// Healths with keys of their own, up to the most food can restore.
+const HEALTHS: usize = u8::MAX as usize + 1;
+
+struct Keys {
+    heads: Vec<u64>,
+    bodies: Vec<u64>,
+    healths: Vec<u64>,
+    food: Vec<u64>,
+    hazards: Vec<u64>,
+}
+
+impl Keys {
+    fn new(seed: u64) -> Keys {
+        let mut rng = Rng::new(seed);
+        let mut keys = |n: usize| (0..n).map(|_| rng.next_u64()).collect();
+        Keys {
+            heads: keys(SNAKES * SQUARES),
+            bodies: keys(SNAKES * SQUARES),
+            healths: keys(SNAKES * HEALTHS),
+            food: keys(SQUARES),
+            hazards: keys(SQUARES),
+        }
+    }
+}
+
+lazy_static! {
+    static ref KEYS: Keys = Keys::new(0x2f6b_9d2e_1c4a_8e35);
+}
+
+/// Zobrist hash of everything the search looks at: every living snake's
+/// head, body and health, the food and the hazards. Body segments are keyed
+/// by their distance from the head as well, so bodies covering the same
+/// squares in another order hash differently.
+pub(super) fn hash(board: &Board) -> u64 {
+    let keys = &*KEYS;
+    let game = &board.game;
+    let mut hash = 0;
+    for (i, snake) in board.snakes.iter().enumerate() {
+        if !snake.is_alive() {
+            continue;
+        }
+        let s = i % SNAKES;
+        if let Some(head) = game.index(snake.head()) {
+            hash ^= keys.heads[s * SQUARES + head];
+        }
+        for (k, pos) in snake.body.iter().enumerate() {
+            if let Some(sq) = game.index(pos) {
+                hash ^= keys.bodies[s * SQUARES + sq].rotate_left(k as u32 % 64);
+            }
+        }
+        hash ^= keys.healths[s * HEALTHS + snake.health.clamp(0, HEALTHS as isize - 1) as usize];
+    }
+    for sq in board.food.iter() {
+        hash ^= keys.food[sq];
+    }
+    for sq in board.hazards.iter() {
+        hash ^= keys.hazards[sq];
+    }
+    hash
+}
@@ -1,52 +1,290 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicIsize, AtomicU64, AtomicUsize, Ordering};
 use std::fmt;
+use std::io::{self, Write};
+
+#[cfg(test)]
+mod tests;
+
+/// Upper bounds of the move latency buckets, in seconds.
+const LATENCY_BOUNDS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 0.75, 1.0];
+/// Upper bounds of the allocation buckets, from 1KiB to 256MiB.
+const BYTES_BOUNDS: &[f64] = &[
+    1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0, 67108864.0,
+    268435456.0,
+];
+/// Upper bounds of the search depth buckets, in turns.
+const DEPTH_BOUNDS: &[f64] = &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0, 12.0, 16.0, 20.0];
 
 pub static STATS: Stats = Stats{
-    alloc_bytes: Counter("allocator.alloc_bytes", AtomicUsize::new(0)),
-    alloc_calls: Counter("allocator.alloc_calls", AtomicUsize::new(0)),
-    dealloc_bytes: Counter("allocator.dealloc_bytes", AtomicUsize::new(0)),
-    dealloc_calls: Counter("allocator.dealloc_calls", AtomicUsize::new(0)),
+    alloc_bytes: Counter::new("allocator.alloc_bytes", "Bytes allocated."),
+    alloc_calls: Counter::new("allocator.alloc_calls", "Allocations made."),
+    dealloc_bytes: Counter::new("allocator.dealloc_bytes", "Bytes deallocated."),
+    dealloc_calls: Counter::new("allocator.dealloc_calls", "Deallocations made."),
+    requests: Requests::new(),
+    move_latency: Histogram::new(
+        "move.latency_seconds",
+        "Time taken to answer a move.",
+        LATENCY_BOUNDS,
+    ),
+    move_alloc_bytes: Histogram::new(
+        "move.alloc_bytes",
+        "Bytes allocated to answer a move.",
+        BYTES_BOUNDS,
+    ),
+    move_peak_bytes: Histogram::new(
+        "move.peak_bytes",
+        "Most bytes live at once while answering a move.",
+        BYTES_BOUNDS,
+    ),
+    search_depth: Histogram::new(
+        "search.depth",
+        "Depth in turns reached by the search of a move.",
+        DEPTH_BOUNDS,
+    ),
+    search_nodes: Counter::new("search.nodes", "Nodes expanded by searches."),
+    search_seconds: Histogram::new(
+        "search.seconds",
+        "Time spent searching a move.",
+        LATENCY_BOUNDS,
+    ),
+    search_cut_short: Counter::new(
+        "search.cut_short",
+        "Searches stopped by the time budget before the depth limit.",
+    ),
+    search_late: Counter::new(
+        "search.late",
+        "Searches finishing after their request was answered without them.",
+    ),
+    search_late_alloc_bytes: Counter::new(
+        "search.late_alloc_bytes",
+        "Bytes allocated by searches finishing after their request was answered.",
+    ),
+    games: Gauge::new("games", "Games in progress."),
 };
 
+/// Every metric of the process. Nothing here allocates, so the metrics can
+/// be updated from the global allocator.
 #[derive(Debug)]
 pub struct Stats{
     pub alloc_bytes: Counter,
     pub alloc_calls: Counter,
     pub dealloc_calls: Counter,
     pub dealloc_bytes: Counter,
+    pub requests: Requests,
+    pub move_latency: Histogram,
+    pub move_alloc_bytes: Histogram,
+    pub move_peak_bytes: Histogram,
+    pub search_depth: Histogram,
+    pub search_nodes: Counter,
+    pub search_seconds: Histogram,
+    pub search_cut_short: Counter,
+    pub search_late: Counter,
+    pub search_late_alloc_bytes: Counter,
+    pub games: Gauge,
 }
 
-impl fmt::Display for Stats {
+impl Stats {
+    /// Writes every metric in the Prometheus text format.
+    pub fn write_prometheus<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.alloc_bytes.write_prometheus(w)?;
+        self.alloc_calls.write_prometheus(w)?;
+        self.dealloc_bytes.write_prometheus(w)?;
+        self.dealloc_calls.write_prometheus(w)?;
+        self.requests.write_prometheus(w)?;
+        self.move_latency.write_prometheus(w)?;
+        self.move_alloc_bytes.write_prometheus(w)?;
+        self.move_peak_bytes.write_prometheus(w)?;
+        self.search_depth.write_prometheus(w)?;
+        self.search_nodes.write_prometheus(w)?;
+        self.search_seconds.write_prometheus(w)?;
+        self.search_cut_short.write_prometheus(w)?;
+        self.search_late.write_prometheus(w)?;
+        self.search_late_alloc_bytes.write_prometheus(w)?;
+        self.games.write_prometheus(w)
+    }
+}
+
+/// Name of a metric as exported, `search.depth` becomes `snake_search_depth`.
+struct Exported(&'static str);
+
+impl fmt::Display for Exported {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f,
-            "{}, {}, {}, {}",
-            self.alloc_bytes,
-            self.alloc_calls,
-            self.dealloc_bytes,
-            self.dealloc_calls,
-        )
+        write!(f, "snake_")?;
+        for c in self.0.chars() {
+            write!(f, "{}", if c == '.' { '_' } else { c })?;
+        }
+        Ok(())
     }
 }
 
+fn write_header<W: Write>(w: &mut W, name: &Exported, suffix: &str, help: &str, kind: &str) -> io::Result<()> {
+    writeln!(w, "# HELP {}{} {}", name, suffix, help)?;
+    writeln!(w, "# TYPE {}{} {}", name, suffix, kind)
+}
+
 #[derive(Debug)]
-pub struct Counter (&'static str, AtomicUsize);
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicUsize,
+}
 
 impl fmt::Display for Counter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.0, self.get())
+        write!(f, "{}: {}", self.name, self.get())
     }
 }
 
 impl Counter {
+    pub const fn new(name: &'static str, help: &'static str) -> Counter {
+        Counter{ name, help, value: AtomicUsize::new(0) }
+    }
+
     pub fn inc(&self) {
-        self.1.fetch_add(1, Ordering::SeqCst);
+        self.value.fetch_add(1, Ordering::SeqCst);
     }
 
     pub fn inc_by(&self, b: usize) {
-        self.1.fetch_add(b, Ordering::SeqCst);
+        self.value.fetch_add(b, Ordering::SeqCst);
     }
 
     pub fn get(&self) -> usize {
-        self.1.load(Ordering::SeqCst)
+        self.value.load(Ordering::SeqCst)
+    }
+
+    fn write_prometheus<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let name = Exported(self.name);
+        write_header(w, &name, "_total", self.help, "counter")?;
+        writeln!(w, "{}_total {}", name, self.get())
+    }
+}
+
+/// A value that goes up and down.
+#[derive(Debug)]
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicIsize,
+}
+
+impl Gauge {
+    pub const fn new(name: &'static str, help: &'static str) -> Gauge {
+        Gauge{ name, help, value: AtomicIsize::new(0) }
+    }
+
+    pub fn set(&self, value: isize) {
+        self.value.store(value, Ordering::SeqCst);
+    }
+
+    pub fn get(&self) -> isize {
+        self.value.load(Ordering::SeqCst)
+    }
+
+    fn write_prometheus<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let name = Exported(self.name);
+        write_header(w, &name, "", self.help, "gauge")?;
+        writeln!(w, "{} {}", name, self.get())
+    }
+}
+
+/// Most buckets a histogram can have, besides the one past every bound.
+const MAX_BUCKETS: usize = 16;
+
+/// Counts observations into buckets by fixed upper bounds.
+#[derive(Debug)]
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    bounds: &'static [f64],
+    /// Observations per bucket, not cumulative. The one after the last bound
+    /// counts the values past every bound.
+    buckets: [AtomicUsize; MAX_BUCKETS + 1],
+    count: AtomicUsize,
+    /// Sum of the observations, the bits of an `f64`.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub const fn new(name: &'static str, help: &'static str, bounds: &'static [f64]) -> Histogram {
+        assert!(bounds.len() <= MAX_BUCKETS);
+        Histogram{
+            name,
+            help,
+            bounds,
+            buckets: [const { AtomicUsize::new(0) }; MAX_BUCKETS + 1],
+            count: AtomicUsize::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self.bounds.iter().position(|bound| value <= *bound).unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::SeqCst);
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let _ = self.sum.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |sum| {
+            Some((f64::from_bits(sum) + value).to_bits())
+        });
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::SeqCst))
+    }
+
+    fn write_prometheus<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let name = Exported(self.name);
+        write_header(w, &name, "", self.help, "histogram")?;
+        let mut cumulative = 0;
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::SeqCst);
+            writeln!(w, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative)?;
+        }
+        cumulative += self.buckets[self.bounds.len()].load(Ordering::SeqCst);
+        writeln!(w, "{}_bucket{{le=\"+Inf\"}} {}", name, cumulative)?;
+        writeln!(w, "{}_sum {}", name, self.sum())?;
+        writeln!(w, "{}_count {}", name, self.count())
+    }
+}
+
+/// Paths requests are counted by, anything else is counted as `other`.
+const PATHS: [&str; 5] = ["/", "/start", "/move", "/end", "/metrics"];
+/// Statuses requests are counted by, anything else is counted as `other`.
+const CODES: [u16; 6] = [200, 400, 404, 405, 413, 500];
+
+/// Requests answered, by path and status.
+#[derive(Debug)]
+pub struct Requests([[AtomicUsize; CODES.len() + 1]; PATHS.len() + 1]);
+
+impl Requests {
+    const fn new() -> Requests {
+        Requests([const { [const { AtomicUsize::new(0) }; CODES.len() + 1] }; PATHS.len() + 1])
+    }
+
+    pub fn inc(&self, path: &str, code: u16) {
+        let path = PATHS.iter().position(|known| *known == path).unwrap_or(PATHS.len());
+        let code = CODES.iter().position(|known| *known == code).unwrap_or(CODES.len());
+        self.0[path][code].fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn write_prometheus<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let name = Exported("requests");
+        write_header(w, &name, "_total", "Requests answered, by path and status.", "counter")?;
+        for (i, counts) in self.0.iter().enumerate() {
+            let path = PATHS.get(i).copied().unwrap_or("other");
+            for (j, count) in counts.iter().enumerate() {
+                let count = count.load(Ordering::SeqCst);
+                if count == 0 {
+                    continue;
+                }
+                match CODES.get(j) {
+                    Some(code) => writeln!(w, "{}_total{{path=\"{}\",code=\"{}\"}} {}", name, path, code, count)?,
+                    None => writeln!(w, "{}_total{{path=\"{}\",code=\"other\"}} {}", name, path, count)?,
+                }
+            }
+        }
+        Ok(())
     }
 }
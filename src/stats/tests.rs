@@ -0,0 +1,56 @@
+use super::*;
+
+fn prometheus<F: Fn(&mut Vec<u8>) -> io::Result<()>>(write: F) -> String {
+    let mut out = Vec::new();
+    write(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn histogram_buckets_are_cumulative() {
+    let histogram = Histogram::new("move.latency_seconds", "Latency.", &[0.1, 0.5]);
+    for value in [0.05, 0.1, 0.3, 2.0] {
+        histogram.observe(value);
+    }
+    assert_eq!(histogram.count(), 4);
+    assert!((histogram.sum() - 2.45).abs() < 1e-9);
+    assert_eq!(
+        prometheus(|w| histogram.write_prometheus(w)),
+        "# HELP snake_move_latency_seconds Latency.\n\
+         # TYPE snake_move_latency_seconds histogram\n\
+         snake_move_latency_seconds_bucket{le=\"0.1\"} 2\n\
+         snake_move_latency_seconds_bucket{le=\"0.5\"} 3\n\
+         snake_move_latency_seconds_bucket{le=\"+Inf\"} 4\n\
+         snake_move_latency_seconds_sum 2.45\n\
+         snake_move_latency_seconds_count 4\n"
+    );
+}
+
+#[test]
+fn counters_and_gauges_are_exported() {
+    let counter = Counter::new("search.nodes", "Nodes.");
+    counter.inc_by(41);
+    counter.inc();
+    assert_eq!(
+        prometheus(|w| counter.write_prometheus(w)),
+        "# HELP snake_search_nodes_total Nodes.\n\
+         # TYPE snake_search_nodes_total counter\n\
+         snake_search_nodes_total 42\n"
+    );
+
+    let gauge = Gauge::new("games", "Games.");
+    gauge.set(2);
+    gauge.set(1);
+    assert!(prometheus(|w| gauge.write_prometheus(w)).ends_with("\nsnake_games 1\n"));
+
+    let requests = Requests::new();
+    requests.inc("/move", 200);
+    requests.inc("/move", 200);
+    requests.inc("/nope", 404);
+    requests.inc("", 413);
+    let out = prometheus(|w| requests.write_prometheus(w));
+    assert!(out.contains("snake_requests_total{path=\"/move\",code=\"200\"} 2\n"));
+    assert!(out.contains("snake_requests_total{path=\"other\",code=\"404\"} 1\n"));
+    assert!(out.contains("snake_requests_total{path=\"other\",code=\"413\"} 1\n"));
+    assert!(!out.contains("path=\"/start\""));
+}
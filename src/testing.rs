@@ -0,0 +1,50 @@
+//! Helpers shared by the tests of the command line.
+
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+
+pub fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// A file in the temporary directory, removed when dropped. The name has the
+/// process id in it, tests of other runs do not get in the way.
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str) -> TempFile {
+        TempFile(env::temp_dir().join(format!("snake-{}-{}", name, std::process::id())))
+    }
+
+    /// A file holding `content`.
+    pub fn with(name: &str, content: &str) -> TempFile {
+        let file = TempFile::new(name);
+        fs::write(&file.0, content).unwrap();
+        file
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Checks that `run` fails for the flags of every case, with an error
+/// containing the text of the case.
+pub fn assert_rejects<F, T>(cases: &[(&[&str], &str)], run: F)
+where
+    F: Fn(&[String]) -> Result<T, String>,
+    T: Debug,
+{
+    for (flags, expected) in cases {
+        let err = run(&args(flags)).unwrap_err();
+        assert!(err.contains(expected), "{:?}: {}", flags, err);
+    }
+}
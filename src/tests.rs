@@ -0,0 +1,55 @@
+use super::*;
+use std::io::Read;
+
+/// Asks for the snake over a kept open connection, returning the head of the
+/// response.
+fn get(stream: &mut TcpStream) -> String {
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nhost: test\r\n\r\n")
+        .unwrap();
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        let n = stream.read(&mut buf).unwrap();
+        assert!(n > 0, "connection closed early");
+        response.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&response).into_owned();
+        let (head, body) = match text.split_once("\r\n\r\n") {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let len: usize = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        if body.len() >= len {
+            return head.to_string();
+        }
+    }
+}
+
+#[test]
+fn serves_more_connections_than_workers() {
+    let mut config = Config::default();
+    config.server.workers = 1;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || accept(listener, config));
+
+    let mut busy = TcpStream::connect(addr).unwrap();
+    assert!(get(&mut busy).contains("connection: keep-alive"));
+
+    // The worker gives the busy connection up once another waits, well
+    // before it would have been idle for long.
+    let mut other = TcpStream::connect(addr).unwrap();
+    other.set_read_timeout(Some(READ_TIMEOUT / 2)).unwrap();
+    let closed = (0..100).any(|_| {
+        let head = get(&mut busy);
+        thread::sleep(Duration::from_millis(5));
+        head.contains("connection: close")
+    });
+    assert!(closed);
+    assert!(get(&mut other).contains("connection: keep-alive"));
+}
@@ -0,0 +1,409 @@
+use super::config::{self, flag_value, Config};
+use super::simulate;
+use super::snake::sim::{self, Player, Remote, Rules};
+use super::writing;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+#[cfg(test)]
+mod tests;
+
+pub const USAGE: &str = "\
+usage: snake tournament VARIANT... [--games N] [--seed N] [--jobs N] [--report FILE]
+                                   [--RULE VALUE]... [--FLAG VALUE]...
+
+Plays seeded games between every pair of variants and reports how each did:
+its score, a draw counting as half a win, with a 95% confidence interval and
+its Elo rating relative to the first variant.
+
+A variant is a strategy, a configuration file ending in .toml or the URL of a
+snake served on this machine, followed by the flags changing it, like
+minimax,max-depth=4,weight-eat=-2. Every seed is played twice, the snakes
+swapping places.
+
+  --games N      games every pair of variants plays, even, 100 by default
+  --seed N       seed of the first games, the others count up, 1 by default
+  --jobs N       games played at once, 1 by default, give --threads as well
+  --report FILE  writes the summary to FILE too
+
+Rules are set as for snake simulate, see snake simulate --help. Other flags
+configure every variant played in process, before the flags of the variant.
+";
+
+struct Options {
+    variants: Vec<String>,
+    rules: Rules,
+    games: usize,
+    seed: u64,
+    jobs: usize,
+    report: Option<String>,
+    config: Vec<String>,
+}
+
+/// A snake taking part in the tournament.
+enum Variant {
+    Remote(String),
+    Local(Box<Config>),
+}
+
+impl Variant {
+    /// Reads the variant `spec` on top of the `shared` flags.
+    fn parse<F>(spec: &str, shared: &[String], env: F) -> Result<Variant, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if spec.contains("://") {
+            Remote::new(spec)?;
+            return Ok(Variant::Remote(spec.to_string()));
+        }
+        let mut pieces = spec.split(',');
+        let base = pieces.next().unwrap_or_default();
+        let mut args = shared.to_vec();
+        if base.ends_with(".toml") {
+            args.push("--config".into());
+        } else {
+            args.push("--strategy".into());
+        }
+        args.push(base.to_string());
+        for piece in pieces {
+            if !piece.contains('=') {
+                return Err(format!("{}: expected FLAG=VALUE, got {:?}", spec, piece));
+            }
+            args.push(format!("--{}", piece));
+        }
+        let config = Config::load(&args, env).map_err(|err| format!("{}: {}", spec, err))?;
+        Ok(Variant::Local(Box::new(config)))
+    }
+
+    fn player(&self) -> Result<Box<dyn Player>, String> {
+        Ok(match self {
+            Variant::Remote(url) => Box::new(Remote::new(url)?),
+            Variant::Local(config) => Box::new(config.runner()?),
+        })
+    }
+}
+
+/// A game between the variants `a` and `b`, `a` being `snake-0` unless
+/// swapped.
+struct Game {
+    a: usize,
+    b: usize,
+    seed: u64,
+    swapped: bool,
+}
+
+impl Game {
+    /// The variants by seat.
+    fn seats(&self) -> [usize; 2] {
+        if self.swapped {
+            [self.b, self.a]
+        } else {
+            [self.a, self.b]
+        }
+    }
+
+    /// The game id sent to the variants, different for each seating so
+    /// remote variants playing both at once keep them apart.
+    fn id(&self) -> String {
+        let [first, second] = self.seats();
+        format!("tournament-{}-{}-{}", first, second, self.seed)
+    }
+}
+
+/// Games of a variant against another, or against all the others.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Record {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+}
+
+impl Record {
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    fn add(&mut self, other: &Record) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    /// The record of the other side.
+    fn reversed(&self) -> Record {
+        Record {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+
+    /// Share of the points won, a draw counting half.
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Half the width of the 95% confidence interval of the score, from the
+    /// spread of the points won a game.
+    fn margin(&self) -> f64 {
+        let games = self.games() as f64;
+        let score = self.score();
+        let squares = (self.wins as f64 + self.draws as f64 / 4.0) / games;
+        1.96 * ((squares - score * score).max(0.0) / games).sqrt()
+    }
+}
+
+/// Plays the tournament given by `args`, writing each game to `out` as it
+/// ends and the summary after the last.
+pub fn run<F, W>(args: &[String], env: F, out: &mut W) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<String>,
+    W: Write,
+{
+    let opts = parse_args(args)?;
+    let mut variants = Vec::new();
+    for spec in &opts.variants {
+        variants.push(Variant::parse(spec, &opts.config, &env)?);
+    }
+    let mut games = Vec::new();
+    for a in 0..variants.len() {
+        for b in a + 1..variants.len() {
+            games.extend((0..opts.games).map(|k| Game {
+                a,
+                b,
+                seed: opts.seed + k as u64 / 2,
+                swapped: k % 2 == 1,
+            }));
+        }
+    }
+
+    let mut records = vec![vec![Record::default(); variants.len()]; variants.len()];
+    let next = AtomicUsize::new(0);
+    let (send, receive) = mpsc::channel();
+    thread::scope(|scope| {
+        let (next, games, variants, rules) = (&next, &games, &variants, &opts.rules);
+        for _ in 0..opts.jobs.min(games.len()) {
+            let send = send.clone();
+            scope.spawn(move || {
+                while let Some(game) = games.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if send.send((game, play(game, variants, rules))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(send);
+
+        for (done, (game, result)) in receive.iter().enumerate() {
+            let (winner, turns) = match result {
+                Ok(played) => played,
+                Err(err) => {
+                    next.store(games.len(), Ordering::Relaxed);
+                    return Err(err);
+                }
+            };
+            let record = &mut records[game.a][game.b];
+            match winner {
+                Some(winner) if winner == game.a => record.wins += 1,
+                Some(_) => record.losses += 1,
+                None => record.draws += 1,
+            }
+            records[game.b][game.a] = records[game.a][game.b].reversed();
+            let result = match winner {
+                Some(winner) => format!("{} won", opts.variants[winner]),
+                None => "draw".to_string(),
+            };
+            let [first, second] = game.seats();
+            writeln!(
+                out,
+                "game {}/{} seed {}: {} vs {}, {} after {} turns",
+                done + 1,
+                games.len(),
+                game.seed,
+                opts.variants[first],
+                opts.variants[second],
+                result,
+                turns
+            )
+            .map_err(writing)?;
+        }
+        Ok(())
+    })?;
+
+    let summary = report(&opts, &records);
+    write!(out, "\n{}", summary).map_err(writing)?;
+    if let Some(path) = &opts.report {
+        fs::write(path, summary).map_err(|err| format!("{}: {}", path, err))?;
+    }
+    Ok(())
+}
+
+/// Plays `game`, giving the variant that won and the turns it took.
+fn play(game: &Game, variants: &[Variant], rules: &Rules) -> Result<(Option<usize>, u16), String> {
+    let seats = game.seats();
+    let mut players = vec![variants[seats[0]].player()?, variants[seats[1]].player()?];
+    let outcome = sim::play(&game.id(), rules, game.seed, &mut players);
+    Ok((outcome.winner.map(|i| seats[i]), outcome.turns))
+}
+
+/// The Elo difference expected to win `score` of the points.
+fn elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+/// The Elo difference of a pair and its 95% confidence interval. Like the
+/// ratings it counts one more game drawn, so a pair won or lost every game
+/// stays finite.
+fn pair_elo(record: &Record) -> (f64, f64, f64) {
+    let prior = Record {
+        draws: record.draws + 1,
+        ..*record
+    };
+    let (score, margin) = (prior.score(), prior.margin());
+    // The interval goes no further than winning or losing every game.
+    let edge = 0.5 / prior.games() as f64;
+    (
+        elo(score),
+        elo((score - margin).max(edge)),
+        elo((score + margin).min(1.0 - edge)),
+    )
+}
+
+/// Elo ratings best explaining every record, relative to the first variant.
+/// Each pair counts one more game drawn, keeping the ratings of variants that
+/// won or lost all their games finite.
+fn ratings(records: &[Vec<Record>]) -> Vec<f64> {
+    let mut strength = vec![1.0; records.len()];
+    for _ in 0..1000 {
+        for i in 0..records.len() {
+            let (mut points, mut weight) = (0.0, 0.0);
+            for (j, record) in records[i].iter().enumerate().filter(|(j, _)| *j != i) {
+                points += record.score() * record.games() as f64 + 0.5;
+                weight += (record.games() + 1) as f64 / (strength[i] + strength[j]);
+            }
+            strength[i] = points / weight;
+        }
+        let first = strength[0];
+        strength.iter_mut().for_each(|s| *s /= first);
+    }
+    strength.iter().map(|s| 400.0 * s.log10()).collect()
+}
+
+/// The summary of the tournament, a line a variant and a line a pair, the
+/// later variant of the pair against the earlier.
+fn report(opts: &Options, records: &[Vec<Record>]) -> String {
+    let names = &opts.variants;
+    let ratings = ratings(records);
+    let pairs = records.len() * (records.len() - 1) / 2;
+    let mut summary = format!(
+        "{} variants, {} games a pair, {} games from seed {}\n\n",
+        names.len(),
+        opts.games,
+        pairs * opts.games,
+        opts.seed
+    );
+
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    writeln!(
+        summary,
+        "{:<width$}  games    won  drawn   lost  score            elo",
+        "variant",
+        width = width
+    )
+    .unwrap();
+    for (i, name) in names.iter().enumerate() {
+        let mut total = Record::default();
+        records[i].iter().for_each(|record| total.add(record));
+        writeln!(
+            summary,
+            "{:<width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>5.1}% ± {:>4.1}%  {:>+5.0}",
+            name,
+            total.games(),
+            total.wins,
+            total.draws,
+            total.losses,
+            total.score() * 100.0,
+            total.margin() * 100.0,
+            ratings[i],
+            width = width
+        )
+        .unwrap();
+    }
+
+    let versus: Vec<(usize, usize)> = (0..names.len())
+        .flat_map(|a| (a + 1..names.len()).map(move |b| (b, a)))
+        .collect();
+    let width = versus
+        .iter()
+        .map(|(b, a)| names[*b].len() + names[*a].len() + 4)
+        .max()
+        .unwrap_or(0);
+    writeln!(
+        summary,
+        "\n{:<width$}  score            elo difference",
+        "pair",
+        width = width
+    )
+    .unwrap();
+    for (b, a) in versus {
+        let record = &records[b][a];
+        let (difference, low, high) = pair_elo(record);
+        writeln!(
+            summary,
+            "{:<width$}  {:>5.1}% ± {:>4.1}%  {:+.0} ({:+.0} to {:+.0})",
+            format!("{} vs {}", names[b], names[a]),
+            record.score() * 100.0,
+            record.margin() * 100.0,
+            difference,
+            low,
+            high,
+            width = width
+        )
+        .unwrap();
+    }
+    summary
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        variants: Vec::new(),
+        rules: Rules::default(),
+        games: 100,
+        seed: 1,
+        jobs: 1,
+        report: None,
+        config: Vec::new(),
+    };
+    let rest = config::split_flags(args, &[], |name, value| {
+        match name {
+            "games" => opts.games = flag_value(name, value)?,
+            "seed" => opts.seed = flag_value(name, value)?,
+            "jobs" => opts.jobs = flag_value(name, value)?,
+            "report" => opts.report = Some(flag_value(name, value)?),
+            _ => return simulate::set_rule(&mut opts.rules, name, value),
+        }
+        Ok(true)
+    })?;
+    opts.variants = rest.positional;
+    opts.config = rest.config;
+    if opts.variants.len() < 2 {
+        return Err("at least two variants are needed, see --help".into());
+    }
+    if opts.games == 0 || opts.jobs == 0 {
+        return Err("--games and --jobs must be at least 1".into());
+    }
+    if opts.games % 2 == 1 {
+        return Err("--games must be even, every seed is played from both sides".into());
+    }
+    opts.rules.validate()?;
+    Ok(opts)
+}
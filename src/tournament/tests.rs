@@ -0,0 +1,143 @@
+use super::super::testing::{self, args, TempFile};
+use super::*;
+
+#[test]
+fn plays_a_tournament() {
+    let report = TempFile::new("tournament");
+    let all = args(&[
+        "greedy",
+        "greedy,weight-eat=-8",
+        "--games",
+        "4",
+        "--max-turns=30",
+        "--jobs",
+        "2",
+        "--threads",
+        "1",
+        "--report",
+        report.path(),
+    ]);
+    let mut out = Vec::new();
+    run(&all, |_| None, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let games = out.lines().filter(|line| line.starts_with("game ")).count();
+    assert_eq!(games, 4, "{}", out);
+    assert!(
+        out.contains("/4 seed 2: greedy,weight-eat=-8 vs greedy, "),
+        "{}",
+        out
+    );
+
+    let summary = fs::read_to_string(report.path()).unwrap();
+    assert!(out.ends_with(&format!("\n\n{}", summary)), "{}", out);
+    assert!(
+        summary.starts_with("2 variants, 4 games a pair, 4 games from seed 1\n"),
+        "{}",
+        summary
+    );
+    assert!(
+        summary.contains("\ngreedy                    4  "),
+        "{}",
+        summary
+    );
+    assert!(
+        summary.contains("\ngreedy,weight-eat=-8 vs greedy  "),
+        "{}",
+        summary
+    );
+}
+
+#[test]
+fn names_each_seating_apart() {
+    let game = |swapped| Game {
+        a: 0,
+        b: 1,
+        seed: 3,
+        swapped,
+    };
+    assert_eq!(game(false).id(), "tournament-0-1-3");
+    assert_eq!(game(true).id(), "tournament-1-0-3");
+}
+
+#[test]
+fn rates_the_records() {
+    let record = Record {
+        wins: 6,
+        draws: 2,
+        losses: 2,
+    };
+    assert_eq!(record.games(), 10);
+    assert!((record.score() - 0.7).abs() < 1e-9);
+    // Points of 1, 0.5 and 0 have a variance of 0.16 around the score.
+    assert!((record.margin() - 1.96 * (0.16f64 / 10.0).sqrt()).abs() < 1e-9);
+    let even = Record {
+        wins: 0,
+        draws: 4,
+        losses: 0,
+    };
+    assert_eq!((even.score(), even.margin()), (0.5, 0.0));
+
+    assert_eq!(elo(0.5), 0.0);
+    assert!((elo(0.75) - 190.85).abs() < 0.01);
+    assert_eq!(elo(0.0), f64::NEG_INFINITY);
+    assert_eq!(elo(1.0), f64::INFINITY);
+
+    // The second won 3 of 4, 3.5 of 5 counting the draw added.
+    let won = Record {
+        wins: 3,
+        draws: 0,
+        losses: 1,
+    };
+    let lost = Record {
+        wins: 1,
+        draws: 0,
+        losses: 3,
+    };
+    let rated = ratings(&[vec![Record::default(), lost], vec![won, Record::default()]]);
+    assert_eq!(rated[0], 0.0);
+    assert!((rated[1] - elo(0.7)).abs() < 0.01, "{:?}", rated);
+
+    // Ratings add up along a chain of variants.
+    let beats = |wins| Record {
+        wins,
+        draws: 0,
+        losses: 10 - wins,
+    };
+    let records = vec![
+        vec![beats(5), beats(4), beats(3)],
+        vec![beats(6), beats(5), beats(4)],
+        vec![beats(7), beats(6), beats(5)],
+    ];
+    let ratings = ratings(&records);
+    assert!(
+        ratings[0] < ratings[1] && ratings[1] < ratings[2],
+        "{:?}",
+        ratings
+    );
+    assert!((ratings[2] - 2.0 * ratings[1]).abs() < 1.0, "{:?}", ratings);
+
+    // A pair won every game has a finite difference, 10.5 of 11 points.
+    let (difference, low, high) = pair_elo(&beats(10));
+    assert!((difference - elo(10.5 / 11.0)).abs() < 1e-9);
+    assert!(low.is_finite() && low < difference);
+    assert!(high.is_finite() && high >= difference);
+}
+
+#[test]
+fn rejects_invalid_input() {
+    let cases: &[(&[&str], &str)] = &[
+        (&["greedy"], "at least two variants"),
+        (&["greedy", "minimax", "--games", "0"], "must be at least 1"),
+        (&["greedy", "minimax", "--games", "3"], "must be even"),
+        (&["greedy", "greedy,max-depth"], "expected FLAG=VALUE"),
+        (
+            &["greedy", "greedy,max-depht=3"],
+            "unknown flag --max-depht",
+        ),
+        (&["greedy", "greediest"], "greediest: search.strategy"),
+        (&["greedy", "missing.toml"], "missing.toml: missing.toml: "),
+        (&["greedy", "https://example.com"], "only http://"),
+        (&["greedy", "minimax", "--height", "0"], "width and height"),
+    ];
+    testing::assert_rejects(cases, |flags| run(flags, |_| None, &mut Vec::new()));
+}